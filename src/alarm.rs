@@ -1,158 +1,614 @@
+use crate::cleanup::StateScoped;
 use crate::clock::Clock;
-use crate::components::{ObjectBundle, Player, Speed};
+use crate::components::{
+    ObjectBundle, Player, Speed, GROUP_ALARM, GROUP_GRANDFATHER, GROUP_PLAYER, GROUP_REFLECTOR,
+};
+use crate::config::GameConfig;
+use crate::difficulty::Difficulty;
+use crate::endless::Escalation;
+use crate::events::{AlarmHitGrandfather, AlarmHitPlayer, AlarmReflected, ClockChanged};
 use crate::grandfather::Grandfather;
-use crate::reflector::Reflector;
-use bevy::core::FixedTimestep;
+use crate::player::nearest_living_player;
+use crate::reflector::{Reflector, ReflectorState};
+use crate::simulation::{add_gameplay_systems, GameplaySystem, Interpolated, SimulationTime};
+use bevy::math::{const_vec2, Mat2};
 use bevy::prelude::*;
+use bevy_rapier2d::physics::PhysicsSystems;
 use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{loading::TextureAssets, GameState};
 
 #[derive(Component)]
 pub struct Alarm;
 
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AlarmKind {
+    /// Steers straight at the nearest player.
+    Homing,
+    /// Flies in a straight line at its initial velocity.
+    StraightShot,
+    /// Weaves side to side around its initial heading.
+    SineWave,
+    /// Circles a player while slowly closing in.
+    Orbiting,
+    /// Homes like a normal alarm, but breaks into a fan of shots when reflected.
+    Splitting,
+    /// A big, slow homing alarm.
+    Heavy,
+}
+
+impl AlarmKind {
+    /// Multiplier applied to the configured alarm speed.
+    fn speed_scale(self) -> f32 {
+        match self {
+            AlarmKind::Homing | AlarmKind::Splitting => 1.0,
+            AlarmKind::StraightShot => 1.25,
+            AlarmKind::SineWave => 0.9,
+            AlarmKind::Orbiting => 0.8,
+            AlarmKind::Heavy => 0.4,
+        }
+    }
+
+    /// Size relative to a standard alarm, applied to both sprite and collider.
+    fn size_scale(self) -> f32 {
+        match self {
+            AlarmKind::StraightShot => 0.75,
+            AlarmKind::Heavy => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    fn tint(self) -> Color {
+        match self {
+            AlarmKind::Homing => Color::WHITE,
+            AlarmKind::StraightShot => Color::rgb(1.0, 0.6, 0.6),
+            AlarmKind::SineWave => Color::rgb(0.6, 0.8, 1.0),
+            AlarmKind::Orbiting => Color::rgb(0.7, 1.0, 0.6),
+            AlarmKind::Splitting => Color::rgb(1.0, 0.9, 0.4),
+            AlarmKind::Heavy => Color::rgb(0.6, 0.6, 0.6),
+        }
+    }
+}
+
 #[derive(Component)]
 struct Reflected(pub bool);
 
-#[derive(Bundle)]
-struct AlarmBundle {
-    reflected: Reflected,
-    speed: Speed,
-    _alarm: Alarm,
+// movement behaviours, picked from the alarm kind at spawn
+#[derive(Component)]
+struct Homing;
+
+#[derive(Component)]
+struct Heading(Vec2);
 
-    #[bundle]
-    object: ObjectBundle,
+#[derive(Component)]
+struct SineWave {
+    heading: Vec2,
+    amplitude: f32,
+    frequency: f32,
+    elapsed: f32,
+}
+
+#[derive(Component)]
+struct Orbit {
+    /// The player being circled, swapped for the nearest one still playing
+    /// if their clock runs out.
+    target: Option<Entity>,
+    angle: f32,
+    radius: f32,
+    angular_speed: f32,
+}
+
+use bundle::AlarmBundle;
+
+// `#[derive(Bundle)]` in bevy 0.6 forgets each field, which clippy flags as
+// `forget_non_drop`; the allow has to sit on a module to reach the derived impl
+#[allow(clippy::forget_non_drop)]
+mod bundle {
+    use super::*;
+
+    #[derive(Bundle)]
+    pub(super) struct AlarmBundle {
+        pub(super) kind: AlarmKind,
+        pub(super) reflected: Reflected,
+        pub(super) speed: Speed,
+        pub(super) _alarm: Alarm,
+
+        #[bundle]
+        pub(super) object: ObjectBundle,
+    }
 }
 
-pub struct SpawnAlarmEvent(pub Vec3);
+pub struct SpawnAlarmEvent {
+    pub position: Vec3,
+    /// Initial velocity in pixels per second. Zero aims the alarm at the
+    /// nearest player at its kind's speed.
+    pub velocity: Vec2,
+    pub kind: AlarmKind,
+    /// Spawns the alarm already reflected, so it ignores the reflector.
+    pub reflected: bool,
+}
 
 pub struct AlarmPlugin;
 
 impl Plugin for AlarmPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<SpawnAlarmEvent>()
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing)
-                    .with_system(spawn_alarm)
-                    .with_system(move_alarm)
-                    .with_system(handle_collisions),
-            )
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing)
-                    .with_run_criteria(FixedTimestep::step(10.0))
-                    .with_system(cleanup_alarms),
-            );
+        app.add_event::<SpawnAlarmEvent>();
+        add_gameplay_systems(
+            app,
+            SystemSet::new()
+                .with_system(spawn_alarm.after(GameplaySystem::Spawn))
+                .with_system(
+                    move_homing
+                        .label(GameplaySystem::Movement)
+                        .before(PhysicsSystems::StepWorld),
+                )
+                .with_system(
+                    move_straight
+                        .label(GameplaySystem::Movement)
+                        .before(PhysicsSystems::StepWorld),
+                )
+                .with_system(
+                    move_sine_wave
+                        .label(GameplaySystem::Movement)
+                        .before(PhysicsSystems::StepWorld),
+                )
+                .with_system(
+                    move_orbiting
+                        .label(GameplaySystem::Movement)
+                        .before(PhysicsSystems::StepWorld),
+                )
+                .with_system(
+                    reflect_alarms
+                        .label(GameplaySystem::Reflect)
+                        .after(GameplaySystem::Reflector)
+                        .after(GameplaySystem::Movement)
+                        .after(PhysicsSystems::StepWorld),
+                )
+                .with_system(
+                    handle_collisions
+                        .label(GameplaySystem::Hits)
+                        .after(GameplaySystem::Reflect),
+                )
+                .with_system(
+                    cleanup_alarms
+                        .label(GameplaySystem::Outcome)
+                        .after(GameplaySystem::Hits),
+                ),
+        );
     }
 }
 
+const ALARM_SIZE: Vec2 = const_vec2!([32.0, 32.0]);
+const ALARM_HALF_EXTENTS: Vec2 = const_vec2!([20.0, 25.0]);
+
+type PlayerTarget<'a> = (Entity, &'a RigidBodyPositionComponent, &'a Clock);
+
+/// The living player nearest to `position`, and where they are.
+fn target_player(
+    q_player: &Query<PlayerTarget, With<Player>>,
+    position: Vec2,
+) -> Option<(Entity, Vec2)> {
+    let players = q_player.iter().map(|(player, rb_pos, clock)| {
+        (
+            player,
+            Vec2::from(rb_pos.position.translation.vector),
+            clock,
+        )
+    });
+    nearest_living_player(players, position)
+}
+
 fn spawn_alarm(
     mut ev_spawn_alarm: EventReader<SpawnAlarmEvent>,
     mut commands: Commands,
     textures: Res<TextureAssets>,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    escalation: Res<Escalation>,
+    q_player: Query<PlayerTarget, With<Player>>,
 ) {
+    let alarm_speed = config.alarm.speed
+        * config.difficulty.scale(*difficulty).alarm_speed
+        * escalation.alarm_speed;
+
     for ev in ev_spawn_alarm.iter() {
-        let x = ev.0.x;
-        let y = ev.0.y;
-        commands
-            .spawn_bundle(AlarmBundle {
-                reflected: Reflected(false),
-                speed: Speed(400.0),
-                object: ObjectBundle {
-                    rigid_body: RigidBodyBundle {
-                        body_type: RigidBodyType::Dynamic.into(),
-                        mass_properties: RigidBodyMassPropsFlags::ROTATION_LOCKED.into(),
-                        position: Vec2::new(x, y).into(),
+        let x = ev.position.x;
+        let y = ev.position.y;
+        let target = target_player(&q_player, ev.position.truncate());
+        let player_pos = target.map_or(Vec2::ZERO, |(_, position)| position);
+        let kind = ev.kind;
+        let speed = alarm_speed * kind.speed_scale();
+        let velocity = if ev.velocity == Vec2::ZERO {
+            (player_pos - ev.position.truncate()).normalize_or_zero() * speed
+        } else {
+            ev.velocity
+        };
+        let half_extents = ALARM_HALF_EXTENTS * kind.size_scale();
+
+        let mut alarm = commands.spawn_bundle(AlarmBundle {
+            kind,
+            reflected: Reflected(ev.reflected),
+            speed: Speed(speed),
+            object: ObjectBundle {
+                rigid_body: RigidBodyBundle {
+                    body_type: RigidBodyType::Dynamic.into(),
+                    mass_properties: RigidBodyMassPropsFlags::ROTATION_LOCKED.into(),
+                    position: Vec2::new(x, y).into(),
+                    ..Default::default()
+                },
+                collider: ColliderBundle {
+                    shape: ColliderShape::cuboid(half_extents.x, half_extents.y).into(),
+                    collider_type: ColliderType::Solid.into(),
+                    flags: ColliderFlags {
+                        active_events: ActiveEvents::CONTACT_EVENTS,
+                        collision_groups: InteractionGroups::new(
+                            GROUP_ALARM,
+                            GROUP_PLAYER | GROUP_REFLECTOR | GROUP_GRANDFATHER,
+                        ),
                         ..Default::default()
-                    },
-                    collider: ColliderBundle {
-                        shape: ColliderShape::cuboid(20.0, 25.0).into(),
-                        collider_type: ColliderType::Solid.into(),
-                        flags: (ActiveEvents::CONTACT_EVENTS).into(),
+                    }
+                    .into(),
+                    ..Default::default()
+                },
+                sprite: SpriteBundle {
+                    sprite: Sprite {
+                        color: kind.tint(),
+                        custom_size: Some(ALARM_SIZE * kind.size_scale()),
                         ..Default::default()
                     },
-                    sprite: SpriteBundle {
-                        texture: textures.alarm_texture.clone(),
-                        transform: Transform {
-                            translation: Vec3::new(x, y, 0.0),
-                            ..Default::default()
-                        },
+                    texture: textures.alarm_texture.clone(),
+                    transform: Transform {
+                        translation: Vec3::new(x, y, 0.0),
                         ..Default::default()
                     },
+                    ..Default::default()
                 },
-                _alarm: Alarm,
-            })
+            },
+            _alarm: Alarm,
+        });
+        alarm
+            .insert(StateScoped(GameState::Playing))
+            .insert(Interpolated::default())
             .insert(ColliderPositionSync::Discrete);
+
+        match kind {
+            _ if ev.reflected => {
+                alarm.insert(Heading(velocity));
+            }
+            AlarmKind::Homing | AlarmKind::Splitting | AlarmKind::Heavy => {
+                alarm.insert(Homing);
+            }
+            AlarmKind::StraightShot => {
+                alarm.insert(Heading(velocity));
+            }
+            AlarmKind::SineWave => {
+                alarm.insert(SineWave {
+                    heading: velocity,
+                    amplitude: 60.0,
+                    frequency: 4.0,
+                    elapsed: 0.0,
+                });
+            }
+            AlarmKind::Orbiting => {
+                let offset = Vec2::new(x, y) - player_pos;
+                let radius = offset.length();
+                alarm.insert(Orbit {
+                    target: target.map(|(player, _)| player),
+                    angle: offset.y.atan2(offset.x),
+                    radius,
+                    angular_speed: speed / radius.max(1.0),
+                });
+            }
+        }
+    }
+}
+
+type ReflectableAlarm<'a> = (
+    Entity,
+    &'a AlarmKind,
+    &'a Speed,
+    &'a mut Reflected,
+    &'a RigidBodyPositionComponent,
+    &'a mut RigidBodyVelocityComponent,
+);
+
+type AlarmMotion<'a> = (
+    &'a Speed,
+    &'a Reflected,
+    &'a RigidBodyPositionComponent,
+    &'a mut RigidBodyVelocityComponent,
+);
+
+/// Steers homing alarms at whichever living player is nearest to each.
+fn move_homing(
+    mut q_alarm: Query<AlarmMotion, (With<Alarm>, With<Homing>)>,
+    q_player: Query<PlayerTarget, With<Player>>,
+    rapier_parameters: Res<RapierConfiguration>,
+) {
+    for (speed, reflected, rb_pos, mut rb_vel) in q_alarm.iter_mut() {
+        if reflected.0 {
+            continue;
+        }
+        let alarm_pos = Vec2::from(rb_pos.position.translation.vector);
+        let player_pos = match target_player(&q_player, alarm_pos) {
+            Some((_, position)) => position,
+            None => continue,
+        };
+        let player_pos: Vector<Real> = player_pos.into();
+        let mut move_delta = player_pos - rb_pos.position.translation.vector;
+        move_delta /= move_delta.magnitude() * rapier_parameters.scale;
+        rb_vel.linvel = move_delta * speed.0;
     }
 }
 
-fn move_alarm(
+fn move_straight(
+    mut q_alarm: Query<(&Heading, &mut RigidBodyVelocityComponent), With<Alarm>>,
+    rapier_parameters: Res<RapierConfiguration>,
+) {
+    for (heading, mut rb_vel) in q_alarm.iter_mut() {
+        rb_vel.linvel = (heading.0 / rapier_parameters.scale).into();
+    }
+}
+
+fn move_sine_wave(
+    mut q_alarm: Query<(&mut SineWave, &Reflected, &mut RigidBodyVelocityComponent), With<Alarm>>,
+    rapier_parameters: Res<RapierConfiguration>,
+    time: Res<SimulationTime>,
+) {
+    for (mut wave, reflected, mut rb_vel) in q_alarm.iter_mut() {
+        if reflected.0 {
+            continue;
+        }
+        wave.elapsed += time.delta_seconds();
+        // derivative of amplitude * sin(frequency * t) along the heading's normal
+        let side = wave.heading.normalize_or_zero().perp()
+            * wave.amplitude
+            * wave.frequency
+            * (wave.frequency * wave.elapsed).cos();
+        rb_vel.linvel = ((wave.heading + side) / rapier_parameters.scale).into();
+    }
+}
+
+fn move_orbiting(
     mut q_alarm: Query<
         (
             &Speed,
+            &mut Orbit,
             &Reflected,
             &RigidBodyPositionComponent,
             &mut RigidBodyVelocityComponent,
         ),
         With<Alarm>,
     >,
-    q_player: Query<&RigidBodyPositionComponent, With<Player>>,
+    q_player: Query<PlayerTarget, With<Player>>,
     rapier_parameters: Res<RapierConfiguration>,
+    time: Res<SimulationTime>,
 ) {
-    let player = q_player.single();
-    for (speed, reflected, rb_pos, mut rb_vel) in q_alarm.iter_mut() {
-        let player_pos = player.position.translation.vector;
-        let alarm_pos = rb_pos.position.translation.vector;
-        let mut move_delta = player_pos - alarm_pos;
-        move_delta /= move_delta.magnitude() * rapier_parameters.scale;
-
+    let dt = time.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+    for (speed, mut orbit, reflected, rb_pos, mut rb_vel) in q_alarm.iter_mut() {
         if reflected.0 {
-            rb_vel.linvel = -move_delta * speed.0;
-        } else {
-            rb_vel.linvel = move_delta * speed.0;
+            continue;
+        }
+        let alarm_pos = Vec2::from(rb_pos.position.translation.vector);
+        let target = orbit
+            .target
+            .and_then(|player| q_player.get(player).ok())
+            .filter(|(_, _, clock)| clock.time > 0.0)
+            .map(|(player, rb_pos, _)| (player, Vec2::from(rb_pos.position.translation.vector)))
+            .or_else(|| target_player(&q_player, alarm_pos));
+        let (player, player_pos) = match target {
+            Some(target) => target,
+            None => continue,
+        };
+        if orbit.target != Some(player) {
+            // carry on from where the alarm is around its new target
+            let offset = alarm_pos - player_pos;
+            orbit.target = Some(player);
+            orbit.angle = offset.y.atan2(offset.x);
+            orbit.radius = offset.length();
         }
+        orbit.angle += orbit.angular_speed * dt;
+        orbit.radius = (orbit.radius - speed.0 * 0.25 * dt).max(0.0);
+        let target = player_pos + Vec2::new(orbit.angle.cos(), orbit.angle.sin()) * orbit.radius;
+        rb_vel.linvel = ((target - alarm_pos) / (dt * rapier_parameters.scale)).into();
     }
 }
 
-fn handle_collisions(
+fn started_contact(event: &ContactEvent) -> Option<(Entity, Entity)> {
+    match event {
+        ContactEvent::Started(h1, h2) => Some((h1.entity(), h2.entity())),
+        ContactEvent::Stopped(_, _) => None,
+    }
+}
+
+/// Bounces alarms off the reflector along its contact normal. A reflect right
+/// after the reflector comes up is a perfect parry and sends the alarm
+/// straight at the nearest grandfather instead.
+#[allow(clippy::too_many_arguments)]
+fn reflect_alarms(
     mut commands: Commands,
+    mut ev_contacts: EventReader<ContactEvent>,
+    mut ev_spawn_alarm: EventWriter<SpawnAlarmEvent>,
+    mut ev_alarm_reflected: EventWriter<AlarmReflected>,
     narrow_phase: Res<NarrowPhase>,
-    q_player: Query<Entity, With<Player>>,
-    q_reflector: Query<Entity, (With<Reflector>, Without<Player>)>,
-    mut q_grandfather: Query<(Entity, &mut Clock), With<Grandfather>>,
-    mut q_alarm: Query<(Entity, &mut Reflected), With<Alarm>>,
-    mut q_clock: Query<&mut Clock, (With<Player>, Without<Grandfather>)>,
+    config: Res<GameConfig>,
+    rapier_parameters: Res<RapierConfiguration>,
+    q_reflector: Query<(&Reflector, &GlobalTransform)>,
+    q_grandfather: Query<(&Clock, &Transform), With<Grandfather>>,
+    mut q_alarm: Query<ReflectableAlarm, With<Alarm>>,
 ) {
-    let reflector = q_reflector.single();
-    let player = q_player.single();
-    for (alarm, mut reflected) in q_alarm.iter_mut() {
-        if let Some(contact_pair) = narrow_phase.contact_pair(reflector.handle(), alarm.handle()) {
-            if contact_pair.has_any_active_contact {
-                reflected.0 = true;
-            }
+    for (e1, e2) in ev_contacts.iter().filter_map(started_contact) {
+        let (alarm_entity, reflector_entity) = if q_alarm.get(e1).is_ok() {
+            (e1, e2)
+        } else {
+            (e2, e1)
+        };
+        let (reflector, reflector_transform) = match q_reflector.get(reflector_entity) {
+            Ok(reflector) => reflector,
+            Err(_) => continue,
+        };
+        let (alarm, kind, speed, mut reflected, rb_pos, mut rb_vel) =
+            match q_alarm.get_mut(alarm_entity) {
+                Ok(alarm) => alarm,
+                Err(_) => continue,
+            };
+        if reflected.0 || reflector.state != ReflectorState::Active {
+            continue;
         }
-        if let Some(contact_pair) = narrow_phase.contact_pair(player.handle(), alarm.handle()) {
-            if contact_pair.has_any_active_contact {
-                let mut clock = q_clock.single_mut();
-                clock.sub_seconds(30.0);
-                commands.entity(alarm).despawn();
+        let perfect_parry = reflector.active_time <= config.reflector.parry_window;
+
+        let alarm_pos = Vec2::from(rb_pos.position.translation.vector);
+        // the manifold normal points from collider1 to collider2; fall back to
+        // the direction between the two if the contact is already gone
+        let normal = narrow_phase
+            .contact_pair(reflector_entity.handle(), alarm.handle())
+            .and_then(|contact_pair| {
+                let normal = Vec2::from(contact_pair.manifolds.first()?.data.normal);
+                if contact_pair.collider1 == reflector_entity.handle() {
+                    Some(normal)
+                } else {
+                    Some(-normal)
+                }
+            })
+            .unwrap_or_else(|| {
+                (alarm_pos - reflector_transform.translation.truncate()).normalize_or_zero()
+            });
+
+        let mut velocity = if perfect_parry {
+            nearest_grandfather(&q_grandfather, alarm_pos)
+                .map(|target| (target - alarm_pos).normalize_or_zero())
+                .unwrap_or(normal)
+                * speed.0
+                * config.reflector.parry_speed_scale
+        } else {
+            let incoming = Vec2::from(rb_vel.linvel) * rapier_parameters.scale;
+            // only the approaching part of the velocity is mirrored
+            let outgoing = incoming - 2.0 * incoming.dot(normal).min(0.0) * normal;
+            outgoing.try_normalize().unwrap_or(normal) * speed.0
+        };
+        if velocity == Vec2::ZERO {
+            velocity = normal * speed.0;
+        }
+
+        ev_alarm_reflected.send(AlarmReflected {
+            alarm,
+            position: alarm_pos,
+            perfect: perfect_parry,
+        });
+
+        if *kind == AlarmKind::Splitting {
+            for angle in [-0.4_f32, 0.0, 0.4] {
+                ev_spawn_alarm.send(SpawnAlarmEvent {
+                    position: alarm_pos.extend(0.0),
+                    velocity: Mat2::from_angle(angle) * velocity,
+                    kind: AlarmKind::StraightShot,
+                    reflected: true,
+                });
             }
+            commands.entity(alarm).despawn();
+            continue;
         }
-        for (grandfather, mut clock) in q_grandfather.iter_mut() {
-            if let Some(contact_pair) =
-                narrow_phase.contact_pair(grandfather.handle(), alarm.handle())
-            {
-                if contact_pair.has_any_active_contact {
-                    clock.sub_seconds(30.0);
-                    commands.entity(alarm).despawn();
-                }
+
+        reflected.0 = true;
+        rb_vel.linvel = (velocity / rapier_parameters.scale).into();
+        commands
+            .entity(alarm)
+            .remove::<Homing>()
+            .remove::<SineWave>()
+            .remove::<Orbit>()
+            .insert(Heading(velocity));
+    }
+}
+
+fn nearest_grandfather(
+    q_grandfather: &Query<(&Clock, &Transform), With<Grandfather>>,
+    position: Vec2,
+) -> Option<Vec2> {
+    q_grandfather
+        .iter()
+        .filter(|(clock, _)| clock.time > 0.0)
+        .map(|(_, transform)| transform.translation.truncate())
+        .min_by(|a, b| {
+            a.distance_squared(position)
+                .partial_cmp(&b.distance_squared(position))
+                .unwrap()
+        })
+}
+
+type TimedEntity<'a> = (Entity, &'a mut Clock);
+
+#[allow(clippy::too_many_arguments)]
+fn handle_collisions(
+    mut commands: Commands,
+    mut ev_contacts: EventReader<ContactEvent>,
+    mut ev_hit_player: EventWriter<AlarmHitPlayer>,
+    mut ev_hit_grandfather: EventWriter<AlarmHitGrandfather>,
+    mut ev_clock_changed: EventWriter<ClockChanged>,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    mut q_player: Query<TimedEntity, (With<Player>, Without<Grandfather>)>,
+    mut q_grandfather: Query<(Entity, &mut Clock, &Grandfather)>,
+    q_alarm: Query<(&Transform, &Reflected), With<Alarm>>,
+) {
+    let hit_penalty = config.player.hit_penalty * config.difficulty.scale(*difficulty).hit_penalty;
+    for (e1, e2) in ev_contacts.iter().filter_map(started_contact) {
+        let (alarm, other) = if q_alarm.get(e1).is_ok() {
+            (e1, e2)
+        } else {
+            (e2, e1)
+        };
+        let (position, reflected) = match q_alarm.get(alarm) {
+            Ok((transform, reflected)) => (transform.translation.truncate(), reflected.0),
+            Err(_) => continue,
+        };
+
+        if let Ok((player, mut clock)) = q_player.get_mut(other) {
+            clock.sub_seconds(hit_penalty);
+            ev_clock_changed.send(ClockChanged {
+                entity: player,
+                delta: -hit_penalty,
+            });
+            ev_hit_player.send(AlarmHitPlayer { player, position });
+            commands.entity(alarm).despawn();
+        } else if let Ok((grandfather, mut clock, boss)) = q_grandfather.get_mut(other) {
+            // a grandfather's own alarms pass through it until reflected
+            if !reflected {
+                continue;
+            }
+            // alarms break harmlessly against a grandfather changing phase
+            if boss.is_invulnerable() {
+                commands.entity(alarm).despawn();
+                continue;
             }
+            clock.sub_seconds(config.grandfather.hit_penalty);
+            ev_clock_changed.send(ClockChanged {
+                entity: grandfather,
+                delta: -config.grandfather.hit_penalty,
+            });
+            ev_hit_grandfather.send(AlarmHitGrandfather {
+                which: grandfather,
+                position,
+            });
+            commands.entity(alarm).despawn();
         }
     }
 }
 
-fn cleanup_alarms(mut commands: Commands, q_alarm: Query<(Entity, &Reflected), With<Alarm>>) {
-    for (alarm, reflected) in q_alarm.iter() {
-        if reflected.0 {
+/// Removes alarms that missed and left the arena, such as reflected alarms
+/// or the stray shots of a bullet pattern.
+fn cleanup_alarms(
+    mut commands: Commands,
+    window: Res<WindowDescriptor>,
+    q_alarm: Query<(Entity, &Transform), With<Alarm>>,
+) {
+    let bounds = Vec2::new(window.width, window.height) / 2.0 + ALARM_SIZE;
+    for (alarm, transform) in q_alarm.iter() {
+        let position = transform.translation.truncate().abs();
+        if position.x > bounds.x || position.y > bounds.y {
             commands.entity(alarm).despawn();
         }
     }
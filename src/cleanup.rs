@@ -0,0 +1,37 @@
+use crate::GameState;
+use bevy::prelude::*;
+
+/// Despawns the entity, along with its children, when its state is exited.
+/// Entities without it, such as the cameras, live across every state.
+#[derive(Component)]
+pub struct StateScoped(pub GameState);
+
+pub struct CleanupPlugin;
+
+impl Plugin for CleanupPlugin {
+    fn build(&self, app: &mut App) {
+        for state in [
+            GameState::Loading,
+            GameState::Menu,
+            GameState::Playing,
+            GameState::GameOver,
+            GameState::GameWin,
+            GameState::Paused,
+            GameState::HighScores,
+        ] {
+            app.add_system_set(
+                SystemSet::on_exit(state.clone()).with_system(despawn_state_scoped(state)),
+            );
+        }
+    }
+}
+
+fn despawn_state_scoped(state: GameState) -> impl FnMut(Commands, Query<(Entity, &StateScoped)>) {
+    move |mut commands, q_scoped| {
+        for (entity, scoped) in q_scoped.iter() {
+            if scoped.0 == state {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
@@ -2,7 +2,8 @@ use bevy::prelude::*;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
-use crate::GameState;
+use crate::events::ClockExpired;
+use crate::simulation::{add_gameplay_systems, GameplaySystem, SimulationTime};
 
 #[derive(Component)]
 pub struct Clock {
@@ -10,6 +11,7 @@ pub struct Clock {
     pub seconds: f32,
     pub time: f32,
     pub speed: f32,
+    pub expired: bool,
 }
 
 impl Clock {
@@ -18,6 +20,7 @@ impl Clock {
             minutes: 0.0,
             seconds: 0.0,
             speed: 1.0,
+            expired: false,
             time,
         }
     }
@@ -28,25 +31,51 @@ impl Clock {
 
 impl Display for Clock {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let minutes = self.time as u32 / 60;
-        let seconds = self.time as u32 % 60;
-        let fmt_time = format!("{:02}:{:02}", minutes, seconds);
-        write!(f, "{}", fmt_time)
+        write!(f, "{}", format_time(self.time))
     }
 }
 
+/// Formats whole seconds as `mm:ss`.
+pub fn format_time(time: f32) -> String {
+    let minutes = time as u32 / 60;
+    let seconds = time as u32 % 60;
+    format!("{:02}:{:02}", minutes, seconds)
+}
+
 pub struct ClockPlugin;
 
 impl Plugin for ClockPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(tick_clock));
+        add_gameplay_systems(
+            app,
+            SystemSet::new()
+                .with_system(tick_clock.label(GameplaySystem::Timers))
+                .with_system(
+                    check_expired
+                        .label(GameplaySystem::Expire)
+                        .label(GameplaySystem::Outcome)
+                        .after(GameplaySystem::Hits),
+                ),
+        );
     }
 }
 
-fn tick_clock(mut q_clocks: Query<&mut Clock>, time: Res<Time>) {
+fn tick_clock(mut q_clocks: Query<&mut Clock>, time: Res<SimulationTime>) {
     for mut clock in q_clocks.iter_mut() {
         clock.time -= clock.speed * time.delta_seconds();
         clock.minutes = clock.time / 60.0;
         clock.seconds = clock.time % 60.0;
     }
 }
+
+fn check_expired(
+    mut q_clocks: Query<(Entity, &mut Clock)>,
+    mut ev_clock_expired: EventWriter<ClockExpired>,
+) {
+    for (entity, mut clock) in q_clocks.iter_mut() {
+        if !clock.expired && clock.time <= 0.0 {
+            clock.expired = true;
+            ev_clock_expired.send(ClockExpired { entity });
+        }
+    }
+}
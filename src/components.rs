@@ -23,6 +23,23 @@ pub struct Dash {
 #[derive(Component)]
 pub struct Player;
 
+/// The most players that can share a run.
+pub const MAX_PLAYERS: usize = 2;
+
+/// Which player an entity belongs to, counted from 0. Put on each player and
+/// on their reflector and HUD.
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PlayerId(pub usize);
+
+#[derive(Component)]
+pub struct MainCamera;
+
+// collision groups
+pub const GROUP_PLAYER: u32 = 1 << 0;
+pub const GROUP_REFLECTOR: u32 = 1 << 1;
+pub const GROUP_ALARM: u32 = 1 << 2;
+pub const GROUP_GRANDFATHER: u32 = 1 << 3;
+
 // stats
 #[derive(Component)]
 pub struct Speed(pub f32);
@@ -0,0 +1,355 @@
+use crate::alarm::AlarmKind;
+use crate::difficulty::DifficultyConfig;
+use crate::endless::EndlessConfig;
+use crate::grandfather::GrandfatherTexture;
+use crate::pattern::PatternLibrary;
+use crate::wave::WaveTimeline;
+use bevy::asset::{Asset, AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "4c5d7f0e-2b8a-4f61-9a53-6f1d2c8e9b17"]
+pub struct GameConfig {
+    /// Gameplay ticks per second, whatever the frame rate.
+    pub tick_rate: f32,
+    /// Seeds every run with this instead of the day's date.
+    pub seed: Option<u64>,
+    pub player: PlayerConfig,
+    pub alarm: AlarmConfig,
+    pub grandfather: GrandfatherConfig,
+    /// The bosses of the stage. The run is won once all their clocks run out.
+    pub grandfathers: Vec<GrandfatherSpec>,
+    pub reflector: ReflectorConfig,
+    /// How each difficulty scales the values above.
+    pub difficulty: DifficultyConfig,
+    pub endless: EndlessConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerConfig {
+    pub clock: f32,
+    pub speed: f32,
+    pub dash_speed: f32,
+    pub dash_duration: f32,
+    pub hit_penalty: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AlarmConfig {
+    pub speed: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GrandfatherConfig {
+    pub hit_penalty: f32,
+    /// Seconds a grandfather shrugs off alarms and holds its fire while
+    /// moving on to its next phase.
+    pub phase_transition: f32,
+}
+
+/// One grandfather on the stage.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GrandfatherSpec {
+    pub name: String,
+    pub texture: GrandfatherTexture,
+    /// Centre, in pixels from the middle of the stage.
+    pub position: (f32, f32),
+    /// Half the width and height of the collider.
+    pub half_extents: (f32, f32),
+    pub clock: f32,
+    /// Where alarms are thrown from, relative to `position`. The wave's spawn
+    /// offsets are added to it.
+    pub spawn_offset: (f32, f32),
+    /// Phases after the opening one, in the order they are reached.
+    pub phases: Vec<PhaseSpec>,
+    pub movement: MovementSpec,
+    /// Backs away from the player instead once its clock runs low.
+    pub retreat: Option<RetreatSpec>,
+    /// Name of the bullet pattern thrown each time its alarm timer fires, in
+    /// place of the wave's alarms.
+    pub pattern: Option<String>,
+}
+
+/// How a grandfather gets about the stage.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum MovementSpec {
+    /// Stays where it was placed.
+    Still,
+    /// Paces back and forth between two x coordinates at its starting height.
+    Patrol { speed: f32, left: f32, right: f32 },
+    /// Vanishes and reappears at another of the anchor points, picked at
+    /// random, every `interval` seconds.
+    Teleport {
+        anchors: Vec<(f32, f32)>,
+        interval: f32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RetreatSpec {
+    /// Starts once the clock is at or below this fraction of what it started
+    /// with.
+    pub below: f32,
+    pub speed: f32,
+}
+
+/// A change of attack once a grandfather's clock runs low.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PhaseSpec {
+    /// Starts once the clock is at or below this fraction of what it started
+    /// with.
+    pub below: f32,
+    /// How many times faster than the wave's spawn interval alarms are
+    /// thrown.
+    pub spawn_rate: f32,
+    /// Replaces the wave's alarm kinds, unless empty.
+    pub alarms: Vec<AlarmKind>,
+    /// Replaces the grandfather's bullet pattern, or the wave's alarms if it
+    /// has none.
+    pub pattern: Option<String>,
+    /// Colour the sprite is tinted with.
+    pub tint: (f32, f32, f32),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReflectorConfig {
+    /// Seconds after raising the reflector during which a reflect counts as a
+    /// perfect parry.
+    pub parry_window: f32,
+    pub parry_speed_scale: f32,
+    /// Distance from the player's centre to the reflector.
+    pub orbit_radius: f32,
+    /// Seconds the reflector can be held up on a full meter.
+    pub stamina: f32,
+    /// Stamina regained per second while the reflector is down.
+    pub stamina_regen: f32,
+    /// Seconds the reflector stays locked after running out of stamina.
+    pub cooldown: f32,
+}
+
+/// A RON asset that is checked after parsing, before it is handed to the game.
+pub trait ConfigAsset: Asset + DeserializeOwned {
+    /// Returns one message per invalid field.
+    fn validate(&self) -> Result<(), Vec<String>>;
+}
+
+/// Collects a message for every value that is not a positive, finite number.
+pub fn check_positive(errors: &mut Vec<String>, name: &str, value: f32) {
+    if !value.is_finite() || value <= 0.0 {
+        errors.push(format!("{} must be a positive number, got {}", name, value));
+    }
+}
+
+impl ConfigAsset for GameConfig {
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        check_positive(&mut errors, "tick_rate", self.tick_rate);
+        check_positive(&mut errors, "player.clock", self.player.clock);
+        check_positive(&mut errors, "player.speed", self.player.speed);
+        check_positive(&mut errors, "player.dash_speed", self.player.dash_speed);
+        check_positive(
+            &mut errors,
+            "player.dash_duration",
+            self.player.dash_duration,
+        );
+        check_positive(&mut errors, "player.hit_penalty", self.player.hit_penalty);
+        check_positive(&mut errors, "alarm.speed", self.alarm.speed);
+        check_positive(
+            &mut errors,
+            "grandfather.hit_penalty",
+            self.grandfather.hit_penalty,
+        );
+        check_positive(
+            &mut errors,
+            "grandfather.phase_transition",
+            self.grandfather.phase_transition,
+        );
+        if self.grandfathers.is_empty() {
+            errors.push("grandfathers must contain at least one grandfather".to_string());
+        }
+        for (i, grandfather) in self.grandfathers.iter().enumerate() {
+            let (half_width, half_height) = grandfather.half_extents;
+            check_positive(
+                &mut errors,
+                &format!("grandfathers[{}].half_extents.0", i),
+                half_width,
+            );
+            check_positive(
+                &mut errors,
+                &format!("grandfathers[{}].half_extents.1", i),
+                half_height,
+            );
+            check_positive(
+                &mut errors,
+                &format!("grandfathers[{}].clock", i),
+                grandfather.clock,
+            );
+            let mut previous = 1.0;
+            for (j, phase) in grandfather.phases.iter().enumerate() {
+                if !(phase.below > 0.0 && phase.below < previous) {
+                    errors.push(format!(
+                        "grandfathers[{}].phases[{}].below must be between 0 and {}, got {}",
+                        i, j, previous, phase.below
+                    ));
+                }
+                previous = phase.below;
+                check_positive(
+                    &mut errors,
+                    &format!("grandfathers[{}].phases[{}].spawn_rate", i, j),
+                    phase.spawn_rate,
+                );
+            }
+            match &grandfather.movement {
+                MovementSpec::Still => {}
+                MovementSpec::Patrol { speed, left, right } => {
+                    check_positive(
+                        &mut errors,
+                        &format!("grandfathers[{}].movement.speed", i),
+                        *speed,
+                    );
+                    if left >= right {
+                        errors.push(format!(
+                            "grandfathers[{}].movement.left must be less than right",
+                            i
+                        ));
+                    }
+                }
+                MovementSpec::Teleport { anchors, interval } => {
+                    check_positive(
+                        &mut errors,
+                        &format!("grandfathers[{}].movement.interval", i),
+                        *interval,
+                    );
+                    if anchors.is_empty() {
+                        errors.push(format!(
+                            "grandfathers[{}].movement.anchors must not be empty",
+                            i
+                        ));
+                    }
+                }
+            }
+            if let Some(retreat) = &grandfather.retreat {
+                if !(retreat.below > 0.0 && retreat.below <= 1.0) {
+                    errors.push(format!(
+                        "grandfathers[{}].retreat.below must be above 0 and at most 1, got {}",
+                        i, retreat.below
+                    ));
+                }
+                check_positive(
+                    &mut errors,
+                    &format!("grandfathers[{}].retreat.speed", i),
+                    retreat.speed,
+                );
+            }
+        }
+        check_positive(
+            &mut errors,
+            "reflector.parry_window",
+            self.reflector.parry_window,
+        );
+        check_positive(
+            &mut errors,
+            "reflector.parry_speed_scale",
+            self.reflector.parry_speed_scale,
+        );
+        check_positive(
+            &mut errors,
+            "reflector.orbit_radius",
+            self.reflector.orbit_radius,
+        );
+        check_positive(&mut errors, "reflector.stamina", self.reflector.stamina);
+        check_positive(
+            &mut errors,
+            "reflector.stamina_regen",
+            self.reflector.stamina_regen,
+        );
+        check_positive(&mut errors, "reflector.cooldown", self.reflector.cooldown);
+        self.difficulty.validate(&mut errors);
+        self.endless.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Errors collected while loading config files, shown on the loading screen.
+#[derive(Clone, Default)]
+pub struct ConfigErrors(Arc<Mutex<Vec<String>>>);
+
+impl ConfigErrors {
+    pub fn push(&self, error: String) {
+        self.0.lock().unwrap().push(error);
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Parses and validates a RON config file.
+pub fn parse_config<T: ConfigAsset>(bytes: &[u8]) -> Result<T, Vec<String>> {
+    let config = ron::de::from_bytes::<T>(bytes).map_err(|err| vec![err.to_string()])?;
+    config.validate()?;
+    Ok(config)
+}
+
+struct RonConfigLoader<T> {
+    extensions: [&'static str; 1],
+    errors: ConfigErrors,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: ConfigAsset> AssetLoader for RonConfigLoader<T> {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display().to_string();
+            let config = match parse_config::<T>(bytes) {
+                Ok(config) => config,
+                Err(errors) => {
+                    for error in errors.iter() {
+                        self.errors.push(format!("{}: {}", path, error));
+                    }
+                    return Err(anyhow::anyhow!("invalid config {}", path));
+                }
+            };
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+}
+
+fn add_config_asset<T: ConfigAsset>(app: &mut App, extension: &'static str) {
+    let errors = app.world.get_resource::<ConfigErrors>().unwrap().clone();
+    app.add_asset::<T>().add_asset_loader(RonConfigLoader::<T> {
+        extensions: [extension],
+        errors,
+        _marker: PhantomData,
+    });
+}
+
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConfigErrors>();
+        add_config_asset::<GameConfig>(app, "config.ron");
+        add_config_asset::<WaveTimeline>(app, "waves.ron");
+        add_config_asset::<PatternLibrary>(app, "patterns.ron");
+    }
+}
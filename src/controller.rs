@@ -0,0 +1,136 @@
+use crate::cleanup::StateScoped;
+use crate::components::{PlayerId, MAX_PLAYERS};
+use crate::loading::FontAssets;
+use crate::GameState;
+use bevy::input::gamepad::{GamepadEvent, GamepadEventType};
+use bevy::prelude::*;
+
+/// The gamepad each player is using, if any, indexed by [`PlayerId`]. Each
+/// gamepad connected goes to the first player without one; when one
+/// disconnects its player is asked to reconnect it.
+#[derive(Default)]
+pub struct ActiveGamepads(pub [Option<Gamepad>; MAX_PLAYERS]);
+
+impl ActiveGamepads {
+    pub fn get(&self, player: PlayerId) -> Option<Gamepad> {
+        self.0[player.0]
+    }
+}
+
+#[derive(Component)]
+struct DisconnectedPromptUI;
+
+pub struct ControllerPlugin;
+
+impl Plugin for ControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveGamepads>()
+            .add_system(track_gamepads)
+            .add_system(dismiss_prompt);
+    }
+}
+
+/// Hands gamepads out as they connect. Losing one mid-run pauses the game
+/// until its player reconnects it or carries on with the keyboard.
+fn track_gamepads(
+    mut commands: Commands,
+    mut ev_gamepad: EventReader<GamepadEvent>,
+    mut active: ResMut<ActiveGamepads>,
+    mut state: ResMut<State<GameState>>,
+    font_assets: Option<Res<FontAssets>>,
+    q_prompt: Query<Entity, With<DisconnectedPromptUI>>,
+) {
+    for GamepadEvent(gamepad, event) in ev_gamepad.iter() {
+        let slot = active.0.iter().position(|active| *active == Some(*gamepad));
+        match event {
+            GamepadEventType::Connected if slot.is_none() => {
+                let free = match active.0.iter().position(Option::is_none) {
+                    Some(free) => free,
+                    None => continue,
+                };
+                info!("player {} is using gamepad {:?}", free + 1, gamepad);
+                active.0[free] = Some(*gamepad);
+                for prompt in q_prompt.iter() {
+                    commands.entity(prompt).despawn();
+                }
+            }
+            GamepadEventType::Disconnected => {
+                if let Some(slot) = slot {
+                    info!("gamepad {:?} disconnected", gamepad);
+                    active.0[slot] = None;
+                    let current = state.current().clone();
+                    if let Some(font_assets) = font_assets.as_ref() {
+                        spawn_prompt(&mut commands, font_assets, PlayerId(slot), current.clone());
+                    }
+                    if current == GameState::Playing {
+                        state.push(GameState::Paused).ok();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Carries on with the keyboard once any key is pressed.
+fn dismiss_prompt(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    q_prompt: Query<Entity, With<DisconnectedPromptUI>>,
+) {
+    if keys.get_just_pressed().next().is_none() {
+        return;
+    }
+    for prompt in q_prompt.iter() {
+        commands.entity(prompt).despawn();
+    }
+}
+
+/// Shows the prompt over the current screen, until it is dismissed or the
+/// screen is left.
+fn spawn_prompt(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    player: PlayerId,
+    scope: GameState,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(480.0),
+                    left: Val::Px(80.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "Controller disconnected\n".to_string(),
+                        style: TextStyle {
+                            font: font_assets.roboto.clone(),
+                            font_size: 40.0,
+                            color: Color::rgb(0.9, 0.3, 0.3),
+                        },
+                    },
+                    TextSection {
+                        value: format!(
+                            "Reconnect player {}'s controller, or press any key to use the keyboard",
+                            player.0 + 1
+                        ),
+                        style: TextStyle {
+                            font: font_assets.roboto.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    },
+                ],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(scope))
+        .insert(DisconnectedPromptUI);
+}
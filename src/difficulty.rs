@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::config::check_positive;
+
+/// How hard runs are, picked on the main menu. Each one scales the game
+/// config by the matching preset in [`DifficultyConfig`].
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Story,
+    #[default]
+    Normal,
+    Hard,
+    Nightmare,
+    /// The player's own multipliers, from the `custom` preset.
+    Custom,
+}
+
+impl Difficulty {
+    /// The difficulty after this one on the menu, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Story => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Nightmare,
+            Difficulty::Nightmare => Difficulty::Custom,
+            Difficulty::Custom => Difficulty::Story,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Multipliers applied to the game config. Above 1 makes the matching value
+/// bigger, whether that makes the run easier or harder.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DifficultyScale {
+    /// Time the player's clock starts with.
+    pub player_clock: f32,
+    /// Time the player loses to each alarm that hits them.
+    pub hit_penalty: f32,
+    pub alarm_speed: f32,
+    /// Seconds between each grandfather's throws.
+    pub spawn_interval: f32,
+    /// Time each grandfather's clock starts with.
+    pub grandfather_clock: f32,
+}
+
+/// One preset per [`Difficulty`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DifficultyConfig {
+    pub story: DifficultyScale,
+    pub normal: DifficultyScale,
+    pub hard: DifficultyScale,
+    pub nightmare: DifficultyScale,
+    pub custom: DifficultyScale,
+}
+
+impl DifficultyConfig {
+    pub fn scale(&self, difficulty: Difficulty) -> &DifficultyScale {
+        match difficulty {
+            Difficulty::Story => &self.story,
+            Difficulty::Normal => &self.normal,
+            Difficulty::Hard => &self.hard,
+            Difficulty::Nightmare => &self.nightmare,
+            Difficulty::Custom => &self.custom,
+        }
+    }
+
+    pub fn validate(&self, errors: &mut Vec<String>) {
+        let presets = [
+            ("story", &self.story),
+            ("normal", &self.normal),
+            ("hard", &self.hard),
+            ("nightmare", &self.nightmare),
+            ("custom", &self.custom),
+        ];
+        for (name, scale) in presets {
+            let fields = [
+                ("player_clock", scale.player_clock),
+                ("hit_penalty", scale.hit_penalty),
+                ("alarm_speed", scale.alarm_speed),
+                ("spawn_interval", scale.spawn_interval),
+                ("grandfather_clock", scale.grandfather_clock),
+            ];
+            for (field, value) in fields {
+                check_positive(errors, &format!("difficulty.{}.{}", name, field), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Endless mode: grandfathers come back with longer clocks each time they are
+//! beaten and the alarms keep getting faster, until the player's clock runs
+//! out.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::config::{check_positive, GameConfig};
+use crate::simulation::{add_gameplay_systems, GameplaySystem, SimulationTime};
+use crate::GameState;
+
+/// How a run is played, picked on the main menu.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    /// Won once every grandfather's clock runs out.
+    #[default]
+    Standard,
+    /// Survive as long as possible against grandfathers that keep coming back.
+    Endless,
+}
+
+impl GameMode {
+    /// The mode after this one on the menu, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            GameMode::Standard => GameMode::Endless,
+            GameMode::Endless => GameMode::Standard,
+        }
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EndlessConfig {
+    /// How much longer each replacement grandfather's clock is than the one
+    /// it replaces.
+    pub clock_growth: f32,
+    /// Alarm speed gained per minute, as a fraction of the starting speed.
+    pub speed_ramp: f32,
+    /// Throwing rate gained per minute, as a fraction of the starting rate.
+    pub spawn_ramp: f32,
+    /// The most either ramp can multiply its value by.
+    pub max_ramp: f32,
+}
+
+impl EndlessConfig {
+    pub fn validate(&self, errors: &mut Vec<String>) {
+        if !self.clock_growth.is_finite() || self.clock_growth < 1.0 {
+            errors.push(format!(
+                "endless.clock_growth must be at least 1, got {}",
+                self.clock_growth
+            ));
+        }
+        check_positive(errors, "endless.speed_ramp", self.speed_ramp);
+        check_positive(errors, "endless.spawn_ramp", self.spawn_ramp);
+        if !self.max_ramp.is_finite() || self.max_ramp < 1.0 {
+            errors.push(format!(
+                "endless.max_ramp must be at least 1, got {}",
+                self.max_ramp
+            ));
+        }
+    }
+}
+
+/// How much faster alarms fly and are thrown than at the start of the run.
+/// Stays at 1 outside endless mode.
+pub struct Escalation {
+    pub alarm_speed: f32,
+    pub spawn_rate: f32,
+    /// Simulated seconds into the run.
+    elapsed: f32,
+}
+
+impl Default for Escalation {
+    fn default() -> Self {
+        Self {
+            alarm_speed: 1.0,
+            spawn_rate: 1.0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+pub struct EndlessPlugin;
+
+impl Plugin for EndlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameMode>()
+            .init_resource::<Escalation>()
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_escalation));
+        add_gameplay_systems(
+            app,
+            SystemSet::new().with_system(escalate.label(GameplaySystem::Timers)),
+        );
+    }
+}
+
+fn reset_escalation(mut escalation: ResMut<Escalation>) {
+    *escalation = Escalation::default();
+}
+
+fn escalate(
+    mode: Res<GameMode>,
+    config: Res<GameConfig>,
+    time: Res<SimulationTime>,
+    mut escalation: ResMut<Escalation>,
+) {
+    if *mode != GameMode::Endless {
+        return;
+    }
+    escalation.elapsed += time.delta_seconds();
+    let minutes = escalation.elapsed / 60.0;
+    let ramp = |per_minute: f32| (1.0 + per_minute * minutes).min(config.endless.max_ramp);
+    escalation.alarm_speed = ramp(config.endless.speed_ramp);
+    escalation.spawn_rate = ramp(config.endless.spawn_ramp);
+}
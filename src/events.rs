@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+/// An alarm reached the player and cost them time.
+pub struct AlarmHitPlayer {
+    pub player: Entity,
+    pub position: Vec2,
+}
+
+/// The reflector sent an alarm back.
+pub struct AlarmReflected {
+    pub alarm: Entity,
+    pub position: Vec2,
+    pub perfect: bool,
+}
+
+/// An alarm crashed into a grandfather.
+pub struct AlarmHitGrandfather {
+    pub which: Entity,
+    pub position: Vec2,
+}
+
+/// The player started a dash.
+pub struct PlayerDashed {
+    pub player: Entity,
+}
+
+/// A clock gained or lost time outside of its regular ticking.
+pub struct ClockChanged {
+    pub entity: Entity,
+    pub delta: f32,
+}
+
+/// A grandfather moved on to another attack phase, counted from 0 for the one
+/// it starts in.
+pub struct GrandfatherPhaseChanged {
+    pub grandfather: Entity,
+    pub phase: usize,
+}
+
+/// A clock ran out of time.
+pub struct ClockExpired {
+    pub entity: Entity,
+}
+
+pub struct EventsPlugin;
+
+impl Plugin for EventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AlarmHitPlayer>()
+            .add_event::<AlarmReflected>()
+            .add_event::<AlarmHitGrandfather>()
+            .add_event::<GrandfatherPhaseChanged>()
+            .add_event::<PlayerDashed>()
+            .add_event::<ClockChanged>()
+            .add_event::<ClockExpired>();
+    }
+}
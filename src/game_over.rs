@@ -1,6 +1,10 @@
 use bevy::prelude::*;
 
+use crate::cleanup::StateScoped;
+use crate::clock::format_time;
+use crate::input::PlayerInputs;
 use crate::loading::{FontAssets, TextureAssets};
+use crate::stats::RunStats;
 use crate::GameState;
 
 pub struct GameOverPlugin;
@@ -18,38 +22,69 @@ fn setup_menu(
     mut commands: Commands,
     font_assets: Res<FontAssets>,
     texture_assets: Res<TextureAssets>,
+    stats: Res<RunStats>,
 ) {
-    commands.spawn_bundle(UiCameraBundle::default());
-    commands.spawn_bundle(TextBundle {
-        style: Style {
-            position_type: PositionType::Absolute,
-            position: Rect {
-                top: Val::Px(130.0),
-                left: Val::Px(120.0),
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(40.0),
+                    left: Val::Px(120.0),
+                    ..Default::default()
+                },
                 ..Default::default()
             },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "Your clock has run out.".to_string(),
+                    style: TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 70.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                }],
+                alignment: Default::default(),
+            },
             ..Default::default()
-        },
-        text: Text {
-            sections: vec![TextSection {
-                value: "Your clock has run out.".to_string(),
-                style: TextStyle {
-                    font: font_assets.roboto.clone(),
-                    font_size: 70.0,
-                    color: Color::rgb(0.9, 0.9, 0.9),
+        })
+        .insert(StateScoped(GameState::GameOver));
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(140.0),
+                    left: Val::Px(250.0),
+                    ..Default::default()
                 },
-            }],
-            alignment: Default::default(),
-        },
-        ..Default::default()
-    });
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: format!(
+                        "Time wasted: {}\n{}",
+                        format_time(stats.elapsed),
+                        stats.breakdown(1.0)
+                    ),
+                    style: TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 30.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::GameOver));
     commands
         .spawn_bundle(ButtonBundle {
             image: texture_assets.button_texture.clone().into(),
             style: Style {
                 position_type: PositionType::Absolute,
                 position: Rect {
-                    top: Val::Px(300.0),
+                    top: Val::Px(460.0),
                     left: Val::Px(350.0),
                     ..Default::default()
                 },
@@ -58,6 +93,7 @@ fn setup_menu(
             },
             ..Default::default()
         })
+        .insert(StateScoped(GameState::GameOver))
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 style: Style {
@@ -85,21 +121,16 @@ fn setup_menu(
         });
 }
 
-type ButtonInteraction<'a> = (Entity, &'a Interaction, &'a Children);
-
 fn click_play_button(
-    mut commands: Commands,
     mut state: ResMut<State<GameState>>,
-    q_interaction: Query<ButtonInteraction, (Changed<Interaction>, With<Button>)>,
-    q_text: Query<Entity, With<Text>>,
+    mut inputs: ResMut<PlayerInputs>,
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
 ) {
-    for (button, interaction, _) in q_interaction.iter() {
-        if *interaction == Interaction::Clicked {
-            commands.entity(button).despawn();
-            for text in q_text.iter() {
-                commands.entity(text).despawn();
-            }
-            state.set(GameState::Playing).unwrap();
-        }
+    let clicked = q_interaction
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if clicked || inputs.confirm() {
+        inputs.consume_menu_presses();
+        state.set(GameState::Playing).ok();
     }
 }
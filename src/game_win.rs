@@ -1,16 +1,22 @@
 use bevy::prelude::*;
 
+use crate::cleanup::StateScoped;
+use crate::clock::format_time;
+use crate::input::PlayerInputs;
 use crate::loading::{FontAssets, TextureAssets};
+use crate::stats::RunStats;
 use crate::GameState;
 
-struct FastTime {
-    pub minutes: f32,
-    pub seconds: f32,
-    pub time: f32,
-}
+/// How long the summary takes to count up to the run's real figures.
+const COUNT_UP_SECONDS: f32 = 2.0;
+
+struct CountUp(Timer);
+
+#[derive(Component)]
+struct WastedTimeUI;
 
 #[derive(Component)]
-struct FastTimeUI;
+struct BreakdownUI;
 
 pub struct GameWinPlugin;
 
@@ -20,7 +26,7 @@ impl Plugin for GameWinPlugin {
             .add_system_set(
                 SystemSet::on_update(GameState::GameWin)
                     .with_system(click_play_button)
-                    .with_system(update_timer),
+                    .with_system(count_up),
             );
     }
 }
@@ -30,41 +36,38 @@ fn setup_menu(
     font_assets: Res<FontAssets>,
     texture_assets: Res<TextureAssets>,
 ) {
-    commands.spawn_bundle(UiCameraBundle::default());
-    commands.insert_resource(FastTime {
-        minutes: 0.0,
-        seconds: 0.0,
-        time: 800.0,
-    });
-    commands.spawn_bundle(TextBundle {
-        style: Style {
-            position_type: PositionType::Absolute,
-            position: Rect {
-                top: Val::Px(130.0),
-                left: Val::Px(130.0),
+    commands.insert_resource(CountUp(Timer::from_seconds(COUNT_UP_SECONDS, false)));
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(40.0),
+                    left: Val::Px(130.0),
+                    ..Default::default()
+                },
                 ..Default::default()
             },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "You have wasted time".to_string(),
+                    style: TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 70.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                }],
+                alignment: Default::default(),
+            },
             ..Default::default()
-        },
-        text: Text {
-            sections: vec![TextSection {
-                value: "You have wasted time".to_string(),
-                style: TextStyle {
-                    font: font_assets.roboto.clone(),
-                    font_size: 70.0,
-                    color: Color::rgb(0.9, 0.9, 0.9),
-                },
-            }],
-            alignment: Default::default(),
-        },
-        ..Default::default()
-    });
+        })
+        .insert(StateScoped(GameState::GameWin));
     commands
         .spawn_bundle(TextBundle {
             style: Style {
                 position_type: PositionType::Absolute,
                 position: Rect {
-                    top: Val::Px(220.0),
+                    top: Val::Px(120.0),
                     left: Val::Px(350.0),
                     ..Default::default()
                 },
@@ -72,7 +75,7 @@ fn setup_menu(
             },
             text: Text {
                 sections: vec![TextSection {
-                    value: "99:99".to_string(),
+                    value: format_time(0.0),
                     style: TextStyle {
                         font: font_assets.roboto.clone(),
                         font_size: 50.0,
@@ -83,14 +86,41 @@ fn setup_menu(
             },
             ..Default::default()
         })
-        .insert(FastTimeUI);
+        .insert(StateScoped(GameState::GameWin))
+        .insert(WastedTimeUI);
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(190.0),
+                    left: Val::Px(250.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: String::new(),
+                    style: TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 30.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::GameWin))
+        .insert(BreakdownUI);
     commands
         .spawn_bundle(ButtonBundle {
             image: texture_assets.button_texture.clone().into(),
             style: Style {
                 position_type: PositionType::Absolute,
                 position: Rect {
-                    top: Val::Px(300.0),
+                    top: Val::Px(460.0),
                     left: Val::Px(320.0),
                     ..Default::default()
                 },
@@ -99,6 +129,7 @@ fn setup_menu(
             },
             ..Default::default()
         })
+        .insert(StateScoped(GameState::GameWin))
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 style: Style {
@@ -126,34 +157,35 @@ fn setup_menu(
         });
 }
 
-fn update_timer(
-    mut q_text: Query<&mut Text, With<FastTimeUI>>,
-    mut timer: ResMut<FastTime>,
+/// Counts the summary up from zero to the run's real figures.
+fn count_up(
+    stats: Res<RunStats>,
     time: Res<Time>,
+    mut count_up: ResMut<CountUp>,
+    mut q_wasted: Query<&mut Text, (With<WastedTimeUI>, Without<BreakdownUI>)>,
+    mut q_breakdown: Query<&mut Text, With<BreakdownUI>>,
 ) {
-    timer.time += time.delta_seconds() * 20.0;
-    timer.minutes = (timer.time / 60.0).floor();
-    timer.seconds = (timer.time % 60.0).floor();
+    count_up.0.tick(time.delta());
+    let progress = count_up.0.percent();
 
-    let mut text = q_text.single_mut();
-    text.sections[0].value = format!("{:02}:{:02}", timer.minutes, timer.seconds);
+    for mut text in q_wasted.iter_mut() {
+        text.sections[0].value = format_time(stats.elapsed * progress);
+    }
+    for mut text in q_breakdown.iter_mut() {
+        text.sections[0].value = stats.breakdown(progress);
+    }
 }
 
-type ButtonInteraction<'a> = (Entity, &'a Interaction, &'a Children);
-
 fn click_play_button(
-    mut commands: Commands,
     mut state: ResMut<State<GameState>>,
-    q_interaction: Query<ButtonInteraction, (Changed<Interaction>, With<Button>)>,
-    q_text: Query<Entity, With<Text>>,
+    mut inputs: ResMut<PlayerInputs>,
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
 ) {
-    for (button, interaction, _) in q_interaction.iter() {
-        if *interaction == Interaction::Clicked {
-            commands.entity(button).despawn();
-            for text in q_text.iter() {
-                commands.entity(text).despawn();
-            }
-            state.set(GameState::Playing).unwrap();
-        }
+    let clicked = q_interaction
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if clicked || inputs.confirm() {
+        inputs.consume_menu_presses();
+        state.set(GameState::Playing).ok();
     }
 }
@@ -1,26 +1,108 @@
 use crate::alarm::SpawnAlarmEvent;
+use crate::cleanup::StateScoped;
 use crate::clock::Clock;
+use crate::components::Player;
+use crate::components::{GROUP_ALARM, GROUP_GRANDFATHER, GROUP_PLAYER};
+use crate::config::{GameConfig, MovementSpec, PhaseSpec, RetreatSpec};
+use crate::difficulty::Difficulty;
+use crate::endless::{Escalation, GameMode};
+use crate::events::GrandfatherPhaseChanged;
 use crate::loading::FontAssets;
+use crate::pattern::{PatternLibrary, PatternRun};
+use crate::player::nearest_living_player;
+use crate::rng::GameRng;
+use crate::simulation::{add_gameplay_systems, GameplaySystem, Interpolated, SimulationTime};
+use crate::wave::{CurrentWave, Wave, WaveStarted, WaveTimeline};
 use crate::AlarmPlugin;
-use bevy::{core::FixedTimestep, prelude::*};
+use bevy::prelude::*;
+use bevy_rapier2d::physics::PhysicsSystems;
 use bevy_rapier2d::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{loading::TextureAssets, GameState};
 
+/// Shows the clock of the grandfather it belongs to, just below it.
 #[derive(Component)]
-pub struct GrandSunClockUI;
+struct GrandfatherClockUI {
+    grandfather: Entity,
+    /// Pixels from the grandfather's centre down to the readout.
+    below: f32,
+}
 
+/// How a grandfather gets about the stage.
 #[derive(Component)]
-pub struct GrandMoonClockUI;
+struct Movement {
+    spec: MovementSpec,
+    /// Where it was placed, which a replacement starts from.
+    home: Vec2,
+    retreat: Option<RetreatSpec>,
+    half_extents: Vec2,
+    /// Which way a patrol is walking along x.
+    heading: f32,
+    /// Time until the next teleport.
+    timer: Timer,
+}
 
 #[derive(Component)]
-pub struct Grandfather;
+pub struct Grandfather {
+    /// Where its alarms are thrown from, relative to its position.
+    spawn_offset: Vec2,
+    /// What its clock started at, which phase thresholds are fractions of.
+    full_clock: f32,
+    phases: Vec<PhaseSpec>,
+    /// How many of `phases` have been reached.
+    phase: usize,
+    /// Bullet pattern thrown in place of the wave's alarms.
+    pattern: Option<String>,
+    /// Patterns still being thrown.
+    running: Vec<PatternRun>,
+    alarm_timer: Timer,
+    /// Runs while moving on to a new phase.
+    transition: Option<Timer>,
+}
 
-#[derive(Component)]
-pub struct GrandfatherMoon;
+impl Grandfather {
+    /// Whether alarms currently break against it without costing it time.
+    pub fn is_invulnerable(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    fn current_phase(&self) -> Option<&PhaseSpec> {
+        self.phase.checked_sub(1).map(|i| &self.phases[i])
+    }
+
+    fn current_pattern(&self) -> Option<&str> {
+        self.current_phase()
+            .and_then(|phase| phase.pattern.as_deref())
+            .or(self.pattern.as_deref())
+    }
+}
 
+/// Announces a grandfather's new phase for a few seconds.
 #[derive(Component)]
-pub struct GrandfatherSun;
+struct PhaseAnnouncement(Timer);
+
+/// The art a grandfather is drawn with.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum GrandfatherTexture {
+    Sun,
+    Moon,
+}
+
+impl GrandfatherTexture {
+    fn handle(self, textures: &TextureAssets) -> Handle<Image> {
+        match self {
+            GrandfatherTexture::Sun => textures.grandfather_sun.clone(),
+            GrandfatherTexture::Moon => textures.grandfather_moon.clone(),
+        }
+    }
+}
+
+/// Roughly half the width of a clock readout, to centre it under its owner.
+const CLOCK_UI_HALF_WIDTH: f32 = 35.0;
+/// Seconds a new phase stays announced.
+const ANNOUNCEMENT_SECONDS: f32 = 2.5;
 
 pub struct GrandfatherPlugin;
 
@@ -28,176 +110,533 @@ impl Plugin for GrandfatherPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(AlarmPlugin)
             .add_system_set(
-                SystemSet::on_enter(GameState::Playing)
-                    .with_system(spawn_grandfather)
-                    .with_system(spawn_clock_ui),
-            )
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing)
-                    .with_run_criteria(FixedTimestep::step(3.0))
-                    .with_system(spawn_alarm),
+                SystemSet::on_enter(GameState::Playing).with_system(
+                    spawn_grandfathers
+                        .label(GameplaySystem::SpawnGrandfathers)
+                        .after(GameplaySystem::SpawnPlayer),
+                ),
             )
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(handle_game_over)
-                    .with_system(update_clock_ui),
+                    .with_system(update_clock_ui)
+                    .with_system(announce_phase)
+                    .with_system(expire_announcements),
             );
+        add_gameplay_systems(
+            app,
+            SystemSet::new()
+                .with_system(
+                    spawn_alarm
+                        .label(GameplaySystem::Spawn)
+                        .after(GameplaySystem::Timers),
+                )
+                .with_system(
+                    move_grandfathers
+                        .label(GameplaySystem::Movement)
+                        .after(GameplaySystem::Spawn)
+                        .before(PhysicsSystems::StepWorld),
+                )
+                .with_system(
+                    replace_defeated_grandfathers
+                        .label(GameplaySystem::Outcome)
+                        .after(GameplaySystem::Expire),
+                )
+                .with_system(update_phases.after(GameplaySystem::Outcome))
+                .with_system(handle_game_over.after(GameplaySystem::Defeat)),
+        );
     }
 }
 
-fn spawn_grandfather(mut commands: Commands, textures: Res<TextureAssets>) {
-    let sun_x = -350.0;
-    let sun_y = 210.0;
-    let moon_x = 350.0;
-    let moon_y = 200.0;
-    let clock_time = 60.0 * 5.0;
-
-    commands
-        .spawn_bundle(SpriteBundle {
-            texture: textures.grandfather_sun.clone(),
-            transform: Transform {
-                translation: Vec3::new(sun_x, sun_y, 1.0),
+/// Spawns every grandfather in the config, each with its clock shown just
+/// below it.
+fn spawn_grandfathers(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    font_assets: Res<FontAssets>,
+    window: Res<WindowDescriptor>,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    library: Res<PatternLibrary>,
+) {
+    let clock_scale = config.difficulty.scale(*difficulty).grandfather_clock;
+    for spec in config.grandfathers.iter() {
+        let clock = spec.clock * clock_scale;
+        let names = spec
+            .phases
+            .iter()
+            .filter_map(|phase| phase.pattern.as_ref())
+            .chain(spec.pattern.as_ref());
+        for name in names {
+            if !library.patterns.contains_key(name) {
+                warn!(
+                    "{} uses the unknown pattern {:?}, throwing the wave's alarms instead",
+                    spec.name, name
+                );
+            }
+        }
+        let position = Vec2::new(spec.position.0, spec.position.1);
+        let (half_width, half_height) = spec.half_extents;
+        let teleport_interval = match spec.movement {
+            MovementSpec::Teleport { interval, .. } => interval,
+            _ => 0.0,
+        };
+        let grandfather = commands
+            .spawn_bundle(SpriteBundle {
+                texture: spec.texture.handle(&textures),
+                transform: Transform {
+                    translation: position.extend(1.0),
+                    ..Default::default()
+                },
                 ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert_bundle(ColliderBundle {
-            shape: ColliderShape::cuboid(70.0, 80.0).into(),
-            collider_type: ColliderType::Solid.into(),
-            position: Vec2::new(sun_x, sun_y).into(),
-            ..Default::default()
-        })
-        .insert(Clock::new(clock_time))
-        .insert(Grandfather)
-        .insert(GrandfatherSun);
-
-    commands
-        .spawn_bundle(SpriteBundle {
-            texture: textures.grandfather_moon.clone(),
-            transform: Transform {
-                translation: Vec3::new(moon_x, moon_y, 1.0),
+            })
+            .insert(StateScoped(GameState::Playing))
+            .insert(Interpolated::default())
+            .insert_bundle(RigidBodyBundle {
+                body_type: RigidBodyType::KinematicVelocityBased.into(),
+                position: position.into(),
                 ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert_bundle(ColliderBundle {
-            shape: ColliderShape::cuboid(90.0, 80.0).into(),
-            collider_type: ColliderType::Solid.into(),
-            position: Vec2::new(moon_x, moon_y).into(),
-            ..Default::default()
-        })
-        .insert(Clock::new(clock_time))
-        .insert(Grandfather)
-        .insert(GrandfatherMoon);
-}
+            })
+            .insert_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(half_width, half_height).into(),
+                collider_type: ColliderType::Solid.into(),
+                flags: grandfather_collider_flags(),
+                ..Default::default()
+            })
+            .insert(ColliderPositionSync::Discrete)
+            .insert(Movement {
+                spec: spec.movement.clone(),
+                home: position,
+                retreat: spec.retreat.clone(),
+                half_extents: Vec2::new(half_width, half_height),
+                heading: 1.0,
+                timer: Timer::from_seconds(teleport_interval, true),
+            })
+            .insert(Clock::new(clock))
+            .insert(Grandfather {
+                spawn_offset: Vec2::new(spec.spawn_offset.0, spec.spawn_offset.1),
+                full_clock: clock,
+                phases: spec.phases.clone(),
+                phase: 0,
+                pattern: spec.pattern.clone(),
+                running: Vec::new(),
+                // armed from the current wave on the first tick
+                alarm_timer: Timer::default(),
+                transition: None,
+            })
+            .insert(Name::new(spec.name.clone()))
+            .id();
 
-fn spawn_clock_ui(mut commands: Commands, font_assets: Res<FontAssets>) {
-    commands
-        .spawn_bundle(TextBundle {
-            style: Style {
-                position_type: PositionType::Absolute,
-                position: Rect {
-                    top: Val::Px(200.0),
-                    left: Val::Px(60.0),
+        let below = half_height + 30.0;
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: clock_ui_position(&window, position, below),
                     ..Default::default()
                 },
+                text: Text {
+                    sections: vec![TextSection {
+                        value: "00:00".to_string(),
+                        style: bevy::text::TextStyle {
+                            font: font_assets.roboto.clone(),
+                            font_size: 30.0,
+                            color: Color::rgb(1., 1., 1.),
+                        },
+                    }],
+                    alignment: Default::default(),
+                },
                 ..Default::default()
-            },
-            text: Text {
-                sections: vec![TextSection {
-                    value: "00:00".to_string(),
-                    style: bevy::text::TextStyle {
-                        font: font_assets.roboto.clone(),
-                        font_size: 30.0,
-                        color: Color::rgb(1., 1., 1.),
-                    },
-                }],
-                alignment: Default::default(),
-            },
-            ..Default::default()
-        })
-        .insert(GrandSunClockUI);
+            })
+            .insert(StateScoped(GameState::Playing))
+            .insert(GrandfatherClockUI { grandfather, below });
+    }
+}
+
+/// Where a clock readout goes to sit `below` pixels under a point on the
+/// stage. UI is laid out from the top left corner, the stage from its middle.
+fn clock_ui_position(window: &WindowDescriptor, position: Vec2, below: f32) -> Rect<Val> {
+    Rect {
+        top: Val::Px(window.height / 2.0 - position.y + below),
+        left: Val::Px(window.width / 2.0 + position.x - CLOCK_UI_HALF_WIDTH),
+        ..Default::default()
+    }
+}
+
+fn grandfather_collider_flags() -> ColliderFlagsComponent {
+    ColliderFlags {
+        collision_groups: InteractionGroups::new(GROUP_GRANDFATHER, GROUP_ALARM | GROUP_PLAYER),
+        ..Default::default()
+    }
+    .into()
+}
+
+type PlayerTarget<'a> = (Entity, &'a Transform, &'a Clock);
+
+/// Where the living player nearest to `position` is.
+fn target_player(q_player: &Query<PlayerTarget, With<Player>>, position: Vec2) -> Option<Vec2> {
+    let players = q_player
+        .iter()
+        .map(|(player, transform, clock)| (player, transform.translation.truncate(), clock));
+    nearest_living_player(players, position).map(|(_, position)| position)
+}
+
+/// Throws each living grandfather's alarms on its own timer, which runs
+/// faster in later phases. A grandfather with a bullet pattern starts it
+/// instead, and fires the shots of every pattern it has going as they come
+/// due.
+#[allow(clippy::too_many_arguments)]
+fn spawn_alarm(
+    mut ev_spawn_alarm: EventWriter<SpawnAlarmEvent>,
+    mut ev_wave_started: EventReader<WaveStarted>,
+    current_wave: Res<CurrentWave>,
+    timeline: Res<WaveTimeline>,
+    library: Res<PatternLibrary>,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    escalation: Res<Escalation>,
+    time: Res<SimulationTime>,
+    mut rng: ResMut<GameRng>,
+    q_player: Query<PlayerTarget, With<Player>>,
+    mut q_grandfathers: Query<(&mut Grandfather, &Clock, &Transform)>,
+) {
+    let wave_started = ev_wave_started.iter().count() > 0;
+    let wave = &timeline.waves[current_wave.index];
+    let alarm_speed = config.alarm.speed
+        * config.difficulty.scale(*difficulty).alarm_speed
+        * escalation.alarm_speed;
+    for (mut grandfather, clock, transform) in q_grandfathers.iter_mut() {
+        // the first wave can start before any tick runs to hear about it
+        if wave_started || grandfather.alarm_timer.duration().is_zero() {
+            grandfather.alarm_timer = Timer::from_seconds(current_wave.spawn_interval, true);
+        }
+        let spawn_rate = grandfather
+            .current_phase()
+            .map_or(1.0, |phase| phase.spawn_rate)
+            * escalation.spawn_rate;
+        grandfather
+            .alarm_timer
+            .tick(time.delta().mul_f32(spawn_rate));
+        if clock.time <= 0.0 {
+            grandfather.running.clear();
+            continue;
+        }
+        if grandfather.is_invulnerable() {
+            continue;
+        }
+
+        let origin = transform.translation.truncate() + grandfather.spawn_offset;
+        let player = target_player(&q_player, origin);
+        let pattern = grandfather
+            .current_pattern()
+            .and_then(|name| library.patterns.get(name));
+        if grandfather.alarm_timer.just_finished() {
+            match pattern {
+                Some(pattern) => grandfather
+                    .running
+                    .push(PatternRun::new(pattern, origin, player)),
+                None => {
+                    // offsets point towards the middle of the stage
+                    let facing = -transform.translation.x.signum();
+                    throw_wave_alarms(
+                        &mut ev_spawn_alarm,
+                        &grandfather,
+                        wave,
+                        origin,
+                        facing,
+                        &mut rng,
+                    );
+                }
+            }
+        }
+
+        for run in grandfather.running.iter_mut() {
+            let shots = run.advance(time.delta_seconds(), origin, player, alarm_speed);
+            for event in shots {
+                ev_spawn_alarm.send(event);
+            }
+        }
+        grandfather.running.retain(|run| !run.is_finished());
+    }
+}
+
+/// Throws the wave's alarms, one at each of its spawn offsets in turn.
+fn throw_wave_alarms(
+    ev_spawn_alarm: &mut EventWriter<SpawnAlarmEvent>,
+    grandfather: &Grandfather,
+    wave: &Wave,
+    origin: Vec2,
+    facing: f32,
+    rng: &mut GameRng,
+) {
+    let alarms = grandfather
+        .current_phase()
+        .map(|phase| &phase.alarms)
+        .filter(|alarms| !alarms.is_empty())
+        .unwrap_or(&wave.alarms);
+    for i in 0..wave.alarm_count as usize {
+        let (x, y) = wave.spawn_offsets[i % wave.spawn_offsets.len()];
+        let jitter = wave.spawn_jitter;
+        let position = Vec3::new(
+            origin.x + x * facing + rng.gen_range(-jitter..=jitter),
+            origin.y + y + rng.gen_range(-jitter..=jitter),
+            0.0,
+        );
+        let kind = alarms[rng.gen_range(0..alarms.len())];
+        ev_spawn_alarm.send(SpawnAlarmEvent {
+            position,
+            velocity: Vec2::ZERO,
+            kind,
+            reflected: false,
+        });
+    }
+}
+
+/// Moves grandfathers on to their next phase as their clocks run down.
+fn update_phases(
+    config: Res<GameConfig>,
+    time: Res<SimulationTime>,
+    mut ev_phase_changed: EventWriter<GrandfatherPhaseChanged>,
+    mut q_grandfathers: Query<(Entity, &mut Grandfather, &Clock, &mut Sprite)>,
+) {
+    for (entity, mut grandfather, clock, mut sprite) in q_grandfathers.iter_mut() {
+        let transitioned = grandfather
+            .transition
+            .as_mut()
+            .is_some_and(|transition| transition.tick(time.delta()).finished());
+        if transitioned {
+            grandfather.transition = None;
+        }
+        if clock.time <= 0.0 {
+            continue;
+        }
+
+        let fraction = clock.time / grandfather.full_clock;
+        let reached = grandfather
+            .phases
+            .iter()
+            .take_while(|phase| fraction <= phase.below)
+            .count();
+        if reached > grandfather.phase {
+            grandfather.phase = reached;
+            let (r, g, b) = grandfather.phases[reached - 1].tint;
+            sprite.color = Color::rgb(r, g, b);
+            grandfather.transition = Some(Timer::from_seconds(
+                config.grandfather.phase_transition,
+                false,
+            ));
+            ev_phase_changed.send(GrandfatherPhaseChanged {
+                grandfather: entity,
+                phase: reached,
+            });
+        }
+    }
+}
+
+fn announce_phase(
+    mut commands: Commands,
+    mut ev_phase_changed: EventReader<GrandfatherPhaseChanged>,
+    font_assets: Res<FontAssets>,
+    q_name: Query<&Name>,
+    q_announcement: Query<Entity, With<PhaseAnnouncement>>,
+) {
+    let changed = match ev_phase_changed.iter().last() {
+        Some(changed) => changed,
+        None => return,
+    };
+    for announcement in q_announcement.iter() {
+        commands.entity(announcement).despawn_recursive();
+    }
+    let name = q_name
+        .get(changed.grandfather)
+        .map_or("A grandfather", |name| name.as_str());
 
     commands
         .spawn_bundle(TextBundle {
             style: Style {
                 position_type: PositionType::Absolute,
                 position: Rect {
-                    top: Val::Px(200.0),
-                    right: Val::Px(60.0),
+                    top: Val::Px(60.0),
+                    left: Val::Px(250.0),
                     ..Default::default()
                 },
                 ..Default::default()
             },
             text: Text {
                 sections: vec![TextSection {
-                    value: "00:00".to_string(),
+                    value: format!("{} enters phase {}!", name, changed.phase + 1),
                     style: bevy::text::TextStyle {
                         font: font_assets.roboto.clone(),
-                        font_size: 30.0,
-                        color: Color::rgb(1., 1., 1.),
+                        font_size: 36.0,
+                        color: Color::rgb(0.95, 0.8, 0.2),
                     },
                 }],
                 alignment: Default::default(),
             },
             ..Default::default()
         })
-        .insert(GrandMoonClockUI);
+        .insert(StateScoped(GameState::Playing))
+        .insert(PhaseAnnouncement(Timer::from_seconds(
+            ANNOUNCEMENT_SECONDS,
+            false,
+        )));
 }
 
-fn spawn_alarm(
-    mut ev_spawn_alarm: EventWriter<SpawnAlarmEvent>,
-    grandfather_moon: Query<(&Clock, &Transform), With<GrandfatherMoon>>,
-    grandfather_sun: Query<(&Clock, &Transform), With<GrandfatherSun>>,
+fn expire_announcements(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_announcement: Query<(Entity, &mut PhaseAnnouncement)>,
 ) {
-    for (clock, moon) in grandfather_moon.iter() {
-        if clock.time > 0.0 {
-            let moon_pos = moon.translation;
-            let position = Vec3::new(moon_pos.x - 150.0, moon_pos.y - 20.0, 0.0);
-            ev_spawn_alarm.send(SpawnAlarmEvent(position));
+    for (entity, mut announcement) in q_announcement.iter_mut() {
+        if announcement.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
         }
     }
-    for (clock, sun) in grandfather_sun.iter() {
-        if clock.time > 0.0 {
-            let sun_pos = sun.translation;
-            let position = Vec3::new(sun_pos.x + 120.0, sun_pos.y - 20.0, 0.0);
-            ev_spawn_alarm.send(SpawnAlarmEvent(position));
+}
+
+fn update_clock_ui(
+    window: Res<WindowDescriptor>,
+    mut q_text: Query<(&mut Text, &mut Style, &GrandfatherClockUI)>,
+    q_grandfather: Query<(&Clock, &Transform), With<Grandfather>>,
+) {
+    for (mut text, mut style, ui) in q_text.iter_mut() {
+        if let Ok((clock, transform)) = q_grandfather.get(ui.grandfather) {
+            text.sections[0].value = clock.to_string();
+            style.position = clock_ui_position(&window, transform.translation.truncate(), ui.below);
         }
     }
 }
 
-fn update_clock_ui(
-    mut q_sun_text: Query<&mut Text, (With<GrandSunClockUI>, Without<GrandMoonClockUI>)>,
-    mut q_moon_text: Query<&mut Text, (With<GrandMoonClockUI>, Without<GrandSunClockUI>)>,
-    q_sun_clock: Query<&Clock, With<GrandfatherSun>>,
-    q_moon_clock: Query<&Clock, With<GrandfatherMoon>>,
+/// Walks, teleports or backs each grandfather away from the nearest player.
+#[allow(clippy::type_complexity)]
+fn move_grandfathers(
+    window: Res<WindowDescriptor>,
+    rapier_parameters: Res<RapierConfiguration>,
+    time: Res<SimulationTime>,
+    mut rng: ResMut<GameRng>,
+    q_player: Query<PlayerTarget, With<Player>>,
+    mut q_grandfathers: Query<(
+        &Grandfather,
+        &mut Movement,
+        &Clock,
+        &Transform,
+        &mut RigidBodyPositionComponent,
+        &mut RigidBodyVelocityComponent,
+    )>,
 ) {
-    let sun_clock = q_sun_clock.single();
-    let mut text = q_sun_text.single_mut();
-    text.sections[0].value = sun_clock.to_string();
+    for (grandfather, mut movement, clock, transform, mut rb_pos, mut rb_vel) in
+        q_grandfathers.iter_mut()
+    {
+        let position = transform.translation.truncate();
+        let player = target_player(&q_player, position);
+        let retreat_speed = movement
+            .retreat
+            .as_ref()
+            .filter(|retreat| clock.time <= retreat.below * grandfather.full_clock)
+            .map(|retreat| retreat.speed);
 
-    let q_moon_clock = q_moon_clock.single();
-    let mut text = q_moon_text.single_mut();
-    text.sections[0].value = q_moon_clock.to_string();
+        let mut velocity = Vec2::ZERO;
+        if clock.time <= 0.0 {
+            // a stopped clock stays put
+        } else if let (Some(speed), Some(player)) = (retreat_speed, player) {
+            velocity = (position - player).normalize_or_zero() * speed;
+            // backing off the edge of the stage is not an escape
+            let bounds = Vec2::new(window.width, window.height) / 2.0 - movement.half_extents;
+            if position.x.abs() >= bounds.x && velocity.x * position.x > 0.0 {
+                velocity.x = 0.0;
+            }
+            if position.y.abs() >= bounds.y && velocity.y * position.y > 0.0 {
+                velocity.y = 0.0;
+            }
+        } else {
+            let movement = &mut *movement;
+            match &movement.spec {
+                MovementSpec::Still => {}
+                MovementSpec::Patrol { speed, left, right } => {
+                    if position.x <= *left {
+                        movement.heading = 1.0;
+                    } else if position.x >= *right {
+                        movement.heading = -1.0;
+                    }
+                    velocity = Vec2::new(movement.heading * speed, 0.0);
+                }
+                MovementSpec::Teleport { anchors, .. } => {
+                    if movement.timer.tick(time.delta()).just_finished() {
+                        let others: Vec<_> = anchors
+                            .iter()
+                            .map(|&(x, y)| Vec2::new(x, y))
+                            .filter(|anchor| anchor.distance(position) > 1.0)
+                            .collect();
+                        if !others.is_empty() {
+                            let anchor = others[rng.gen_range(0..others.len())];
+                            rb_pos.position = anchor.into();
+                            rb_pos.next_position = rb_pos.position;
+                        }
+                    }
+                }
+            }
+        }
+        rb_vel.linvel = (velocity / rapier_parameters.scale).into();
+    }
 }
 
-fn handle_game_over(
-    mut commands: Commands,
-    q_grandfathers: Query<&Clock, With<Grandfather>>,
-    q_entities: Query<Entity>,
-    mut state: ResMut<State<GameState>>,
+/// In endless mode, a grandfather whose clock runs out is replaced by a fresh
+/// one with a longer clock, back where the first one started.
+fn replace_defeated_grandfathers(
+    mode: Res<GameMode>,
+    config: Res<GameConfig>,
+    mut ev_phase_changed: EventWriter<GrandfatherPhaseChanged>,
+    mut q_grandfathers: Query<(
+        Entity,
+        &mut Grandfather,
+        &mut Clock,
+        &mut Sprite,
+        &Movement,
+        &mut RigidBodyPositionComponent,
+    )>,
 ) {
-    let mut dead_count = 0;
-    for clock in q_grandfathers.iter() {
-        if clock.time <= 0.0 {
-            dead_count += 1;
+    if *mode != GameMode::Endless {
+        return;
+    }
+    for (entity, mut grandfather, mut clock, mut sprite, movement, mut rb_pos) in
+        q_grandfathers.iter_mut()
+    {
+        if clock.time > 0.0 {
+            continue;
         }
+        grandfather.full_clock *= config.endless.clock_growth;
+        *clock = Clock::new(grandfather.full_clock);
+        grandfather.phase = 0;
+        grandfather.running.clear();
+        // arrives the same way it would change phase, shrugging off alarms
+        grandfather.transition = Some(Timer::from_seconds(
+            config.grandfather.phase_transition,
+            false,
+        ));
+        sprite.color = Color::WHITE;
+        rb_pos.position = movement.home.into();
+        rb_pos.next_position = rb_pos.position;
+        ev_phase_changed.send(GrandfatherPhaseChanged {
+            grandfather: entity,
+            phase: 0,
+        });
     }
+}
 
-    if dead_count == 2 {
-        for entity in q_entities.iter() {
-            commands.entity(entity).despawn_recursive();
+/// Wins the run once every grandfather's clock has run out. There is no
+/// winning endless mode.
+fn handle_game_over(
+    mode: Res<GameMode>,
+    q_grandfathers: Query<&Clock, With<Grandfather>>,
+    mut state: ResMut<State<GameState>>,
+    mut time: ResMut<SimulationTime>,
+) {
+    if *mode == GameMode::Standard
+        && !q_grandfathers.is_empty()
+        && q_grandfathers.iter().all(|clock| clock.time <= 0.0)
+    {
+        // a run the players lost on this same tick stays lost
+        if state.set(GameState::GameWin).is_ok() {
+            time.stop();
         }
-        state.set(GameState::GameWin).unwrap();
     }
 }
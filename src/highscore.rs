@@ -0,0 +1,381 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cleanup::StateScoped;
+use crate::clock::format_time;
+use crate::endless::GameMode;
+use crate::input::PlayerInputs;
+use crate::loading::{FontAssets, TextureAssets};
+use crate::stats::RunStats;
+use crate::storage;
+use crate::GameState;
+
+const HIGH_SCORES_FILE: &str = "highscores.ron";
+/// How many runs of each kind the high-score screen lists.
+const TABLE_ROWS: usize = 5;
+
+/// A finished run, as kept in the save file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub won: bool,
+    /// Simulated seconds the run lasted.
+    pub time: f32,
+    /// What was left on the player's clock at the end.
+    pub remaining: f32,
+    pub difficulty: String,
+    /// Seconds since the Unix epoch.
+    pub date: u64,
+    /// Runs saved before there were modes were all standard runs.
+    #[serde(default)]
+    pub mode: GameMode,
+    /// Seconds survived plus seconds drained, which endless runs are ranked
+    /// by.
+    #[serde(default)]
+    pub score: f32,
+}
+
+/// Every run finished on this machine.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct HighScores {
+    pub runs: Vec<RunRecord>,
+}
+
+impl HighScores {
+    /// Reads the saved runs, or starts an empty table.
+    pub fn load() -> Self {
+        let saved = match storage::load(HIGH_SCORES_FILE) {
+            Some(saved) => saved,
+            None => return Self::default(),
+        };
+        ron::de::from_str(&saved).unwrap_or_else(|err| {
+            warn!("ignoring {}: {}", HIGH_SCORES_FILE, err);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::save(HIGH_SCORES_FILE, &contents));
+        if let Err(err) = result {
+            warn!("could not save {}: {}", HIGH_SCORES_FILE, err);
+        }
+    }
+
+    fn standard_runs(&self) -> impl Iterator<Item = &RunRecord> {
+        self.runs
+            .iter()
+            .filter(|run| run.mode == GameMode::Standard)
+    }
+
+    /// Standard wins, fastest first.
+    pub fn best_times(&self) -> Vec<&RunRecord> {
+        let mut wins: Vec<_> = self.standard_runs().filter(|run| run.won).collect();
+        wins.sort_by(|a, b| a.time.total_cmp(&b.time));
+        wins
+    }
+
+    /// Standard losses, longest lasting first.
+    pub fn longest_runs(&self) -> Vec<&RunRecord> {
+        let mut losses: Vec<_> = self.standard_runs().filter(|run| !run.won).collect();
+        losses.sort_by(|a, b| b.time.total_cmp(&a.time));
+        losses
+    }
+
+    /// Endless runs, highest score first.
+    pub fn endless_scores(&self) -> Vec<&RunRecord> {
+        let mut runs: Vec<_> = self
+            .runs
+            .iter()
+            .filter(|run| run.mode == GameMode::Endless)
+            .collect();
+        runs.sort_by(|a, b| b.score.total_cmp(&a.score));
+        runs
+    }
+
+    /// Adds a run, returning whether it is the fastest win or the highest
+    /// endless score so far.
+    pub fn record(&mut self, run: RunRecord) -> bool {
+        let record = match run.mode {
+            GameMode::Standard => {
+                run.won
+                    && self
+                        .best_times()
+                        .first()
+                        .is_none_or(|best| run.time < best.time)
+            }
+            GameMode::Endless => self
+                .endless_scores()
+                .first()
+                .is_none_or(|best| run.score > best.score),
+        };
+        self.runs.push(run);
+        record
+    }
+}
+
+/// Whether the run that just ended set a new best time.
+#[derive(Default)]
+pub struct NewRecord(pub bool);
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+enum HighScoreSystem {
+    Record,
+}
+
+#[derive(Component)]
+struct BackButton;
+
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HighScores>()
+            .init_resource::<NewRecord>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(record_run(false).label(HighScoreSystem::Record))
+                    .with_system(spawn_record_banner.after(HighScoreSystem::Record)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameWin)
+                    .with_system(record_run(true).label(HighScoreSystem::Record))
+                    .with_system(spawn_record_banner.after(HighScoreSystem::Record)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::HighScores).with_system(setup_high_scores),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::HighScores).with_system(click_back_button),
+            );
+    }
+}
+
+/// Writes the table out whenever a run is added. Left out when running
+/// headless, so tests never touch the real save file.
+pub fn save_high_scores(scores: Res<HighScores>) {
+    if scores.is_changed() && !scores.is_added() {
+        scores.save();
+    }
+}
+
+fn record_run(won: bool) -> impl FnMut(Res<RunStats>, ResMut<HighScores>, ResMut<NewRecord>) {
+    move |stats, mut scores, mut new_record| {
+        if stats.replay {
+            new_record.0 = false;
+            return;
+        }
+        new_record.0 = scores.record(RunRecord {
+            won,
+            time: stats.elapsed,
+            remaining: stats.remaining.max(0.0),
+            difficulty: stats.difficulty.to_string(),
+            date: storage::unix_time(),
+            mode: stats.mode,
+            score: stats.score(),
+        });
+    }
+}
+
+/// Shows the new record on the game over or game win screen, whichever the
+/// run ended on.
+fn spawn_record_banner(
+    mut commands: Commands,
+    new_record: Res<NewRecord>,
+    font_assets: Res<FontAssets>,
+    state: Res<State<GameState>>,
+) {
+    if !new_record.0 {
+        return;
+    }
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(125.0),
+                    left: Val::Px(520.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "New record!".to_string(),
+                    style: TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 40.0,
+                        color: Color::rgb(0.95, 0.8, 0.2),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(state.current().clone()));
+}
+
+fn setup_high_scores(
+    mut commands: Commands,
+    scores: Res<HighScores>,
+    font_assets: Res<FontAssets>,
+    texture_assets: Res<TextureAssets>,
+) {
+    let table = |title: &str, runs: Vec<&RunRecord>, row: fn(&RunRecord) -> String| {
+        let mut lines = vec![title.to_string()];
+        if runs.is_empty() {
+            lines.push("  none yet".to_string());
+        }
+        for (i, run) in runs.into_iter().take(TABLE_ROWS).enumerate() {
+            lines.push(format!("{}. {}", i + 1, row(run)));
+        }
+        lines.join("\n")
+    };
+    let standard_row = |run: &RunRecord| {
+        format!(
+            "{}  ({} left)  {}  {}",
+            format_time(run.time),
+            format_time(run.remaining),
+            run.difficulty,
+            format_date(run.date)
+        )
+    };
+    let endless_row = |run: &RunRecord| {
+        format!(
+            "{:.0}  ({} survived)  {}  {}",
+            run.score,
+            format_time(run.time),
+            run.difficulty,
+            format_date(run.date)
+        )
+    };
+    let column = |left: f32, value: String| TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                top: Val::Px(140.0),
+                left: Val::Px(left),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text {
+            sections: vec![TextSection {
+                value,
+                style: TextStyle {
+                    font: font_assets.roboto.clone(),
+                    font_size: 20.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            }],
+            alignment: Default::default(),
+        },
+        ..Default::default()
+    };
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(40.0),
+                    left: Val::Px(250.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "High Scores".to_string(),
+                    style: TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 70.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::HighScores));
+    commands
+        .spawn_bundle(column(
+            40.0,
+            format!(
+                "{}\n\n{}",
+                table("Best times", scores.best_times(), standard_row),
+                table("Longest losses", scores.longest_runs(), standard_row)
+            ),
+        ))
+        .insert(StateScoped(GameState::HighScores));
+    commands
+        .spawn_bundle(column(
+            420.0,
+            table("Endless", scores.endless_scores(), endless_row),
+        ))
+        .insert(StateScoped(GameState::HighScores));
+    commands
+        .spawn_bundle(ButtonBundle {
+            image: texture_assets.button_texture.clone().into(),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(480.0),
+                    left: Val::Px(350.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(100.0), Val::Px(60.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::HighScores))
+        .insert(BackButton)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        top: Val::Px(12.0),
+                        left: Val::Px(12.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text {
+                    sections: vec![TextSection {
+                        value: "Back".to_string(),
+                        style: TextStyle {
+                            font: font_assets.roboto.clone(),
+                            font_size: 40.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    }],
+                    alignment: Default::default(),
+                },
+                ..Default::default()
+            });
+        });
+}
+
+// Consumes the press, or the menu would see it again after the state change
+// and start a run straight away.
+fn click_back_button(
+    mut state: ResMut<State<GameState>>,
+    mut inputs: ResMut<PlayerInputs>,
+    q_interaction: Query<&Interaction, With<BackButton>>,
+) {
+    let clicked = q_interaction
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if clicked || inputs.confirm() || inputs.pause() {
+        inputs.consume_menu_presses();
+        state.set(GameState::Menu).unwrap();
+    }
+}
+
+/// Formats a Unix timestamp as a `yyyy-mm-dd` date in UTC.
+fn format_date(date: u64) -> String {
+    let (year, month, day) = storage::civil_date(date);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
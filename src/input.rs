@@ -0,0 +1,597 @@
+use crate::components::{MainCamera, Player, PlayerId, MAX_PLAYERS};
+use crate::controller::{ActiveGamepads, ControllerPlugin};
+use crate::storage;
+use crate::GameState;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::{Index, IndexMut};
+
+const STICK_DEADZONE: f32 = 0.3;
+/// Left stick travel ignored before the player starts moving.
+const MOVE_DEADZONE: f32 = 0.2;
+/// Confirms the focused menu button. Kept apart from the rebindable actions so
+/// menus stay usable whatever the bindings are.
+const MENU_CONFIRM: [Binding; 3] = [
+    Binding::Key(KeyCode::Return),
+    Binding::Gamepad(GamepadButtonType::South),
+    Binding::Gamepad(GamepadButtonType::Start),
+];
+/// Move the menu focus up and down, kept apart from the rebindable actions for
+/// the same reason as [`MENU_CONFIRM`].
+const MENU_UP: [Binding; 2] = [
+    Binding::Key(KeyCode::Up),
+    Binding::Gamepad(GamepadButtonType::DPadUp),
+];
+const MENU_DOWN: [Binding; 2] = [
+    Binding::Key(KeyCode::Down),
+    Binding::Gamepad(GamepadButtonType::DPadDown),
+];
+/// How far the left stick has to be pushed up or down to move the menu focus.
+const MENU_STICK_THRESHOLD: f32 = 0.5;
+
+/// What one player asks for this update.
+#[derive(Default, Clone, Debug)]
+pub struct PlayerInput {
+    /// Desired direction and strength, each axis in `-1.0..=1.0`.
+    pub movement: Vec2,
+    /// Dash was pressed and no gameplay tick has used it yet.
+    pub dash: bool,
+    /// The reflector is held up.
+    pub reflect: bool,
+    /// Pause was pressed this update.
+    pub pause: bool,
+    /// A menu button was confirmed this update.
+    pub confirm: bool,
+    /// Moves the menu focus this update: `-1` up the menu, `1` down it.
+    pub menu_step: i32,
+    /// Direction from the player to face the reflector towards, if any.
+    pub aim: Option<Vec2>,
+}
+
+/// What every player asks for this update, indexed by [`PlayerId`]. Filled
+/// from the keyboard, mouse and gamepads by [`DeviceInputPlugin`], or written
+/// directly by tests.
+#[derive(Default, Clone, Debug)]
+pub struct PlayerInputs([PlayerInput; MAX_PLAYERS]);
+
+impl PlayerInputs {
+    pub fn iter(&self) -> impl Iterator<Item = &PlayerInput> {
+        self.0.iter()
+    }
+
+    /// Any player pressed pause this update.
+    pub fn pause(&self) -> bool {
+        self.0.iter().any(|input| input.pause)
+    }
+
+    /// Any player confirmed a menu button this update.
+    pub fn confirm(&self) -> bool {
+        self.0.iter().any(|input| input.confirm)
+    }
+
+    /// Uses up this update's pause, confirm and menu focus presses, so the
+    /// screen they lead to does not see them as well. A dash goes with them,
+    /// since the same button can confirm and dash.
+    pub fn consume_menu_presses(&mut self) {
+        for input in self.0.iter_mut() {
+            input.pause = false;
+            input.confirm = false;
+            input.menu_step = 0;
+            input.dash = false;
+        }
+    }
+}
+
+impl Index<PlayerId> for PlayerInputs {
+    type Output = PlayerInput;
+
+    fn index(&self, player: PlayerId) -> &PlayerInput {
+        &self.0[player.0]
+    }
+}
+
+impl IndexMut<PlayerId> for PlayerInputs {
+    fn index_mut(&mut self, player: PlayerId) -> &mut PlayerInput {
+        &mut self.0[player.0]
+    }
+}
+
+pub struct PlayerInputPlugin;
+
+impl Plugin for PlayerInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerInputs>()
+            .init_resource::<PlayerBindings>()
+            .init_resource::<PendingRebind>()
+            .add_system_to_stage(CoreStage::Last, clear_pressed);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Dash,
+    Reflect,
+    Pause,
+}
+
+/// A physical input that can trigger an [`Action`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// The button on the player's own gamepad.
+    Gamepad(GamepadButtonType),
+}
+
+/// Which inputs trigger each of one player's actions. Loaded from and saved
+/// to `bindings.ron` in the game's data directory, or `bindings-2.ron` for the
+/// second player.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub actions: BTreeMap<Action, Vec<Binding>>,
+}
+
+/// The first player's defaults.
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self::default_for(PlayerId(0))
+    }
+}
+
+impl InputBindings {
+    /// The bindings `player` starts with. Players after the first share the
+    /// keyboard with the first, so they get keys of their own.
+    pub fn default_for(player: PlayerId) -> Self {
+        if player.0 > 0 {
+            return Self::second_player();
+        }
+        use Binding::*;
+        let actions = [
+            (
+                Action::MoveUp,
+                vec![
+                    Key(KeyCode::W),
+                    Key(KeyCode::Up),
+                    Gamepad(GamepadButtonType::DPadUp),
+                ],
+            ),
+            (
+                Action::MoveDown,
+                vec![
+                    Key(KeyCode::S),
+                    Key(KeyCode::Down),
+                    Gamepad(GamepadButtonType::DPadDown),
+                ],
+            ),
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::A),
+                    Key(KeyCode::Left),
+                    Gamepad(GamepadButtonType::DPadLeft),
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::D),
+                    Key(KeyCode::Right),
+                    Gamepad(GamepadButtonType::DPadRight),
+                ],
+            ),
+            (
+                Action::Dash,
+                vec![Key(KeyCode::Space), Gamepad(GamepadButtonType::South)],
+            ),
+            (
+                Action::Reflect,
+                vec![
+                    Key(KeyCode::LShift),
+                    Mouse(MouseButton::Right),
+                    Gamepad(GamepadButtonType::East),
+                    Gamepad(GamepadButtonType::RightTrigger),
+                ],
+            ),
+            (
+                Action::Pause,
+                vec![
+                    Key(KeyCode::Escape),
+                    Key(KeyCode::P),
+                    Gamepad(GamepadButtonType::Start),
+                ],
+            ),
+        ];
+        Self {
+            actions: actions.into_iter().collect(),
+        }
+    }
+
+    fn second_player() -> Self {
+        use Binding::*;
+        let actions = [
+            (
+                Action::MoveUp,
+                vec![Key(KeyCode::I), Gamepad(GamepadButtonType::DPadUp)],
+            ),
+            (
+                Action::MoveDown,
+                vec![Key(KeyCode::K), Gamepad(GamepadButtonType::DPadDown)],
+            ),
+            (
+                Action::MoveLeft,
+                vec![Key(KeyCode::J), Gamepad(GamepadButtonType::DPadLeft)],
+            ),
+            (
+                Action::MoveRight,
+                vec![Key(KeyCode::L), Gamepad(GamepadButtonType::DPadRight)],
+            ),
+            (
+                Action::Dash,
+                vec![Key(KeyCode::O), Gamepad(GamepadButtonType::South)],
+            ),
+            (
+                Action::Reflect,
+                vec![
+                    Key(KeyCode::U),
+                    Gamepad(GamepadButtonType::East),
+                    Gamepad(GamepadButtonType::RightTrigger),
+                ],
+            ),
+            (Action::Pause, vec![Gamepad(GamepadButtonType::Start)]),
+        ];
+        Self {
+            actions: actions.into_iter().collect(),
+        }
+    }
+
+    /// Reads `player`'s saved bindings, falling back to their defaults.
+    pub fn load(player: PlayerId) -> Self {
+        let file = bindings_file(player);
+        let defaults = Self::default_for(player);
+        let saved = match storage::load(&file) {
+            Some(saved) => saved,
+            None => return defaults,
+        };
+        match ron::de::from_str::<Self>(&saved) {
+            Ok(mut bindings) => {
+                // actions added since the file was written keep their defaults
+                for (action, defaults) in defaults.actions {
+                    bindings.actions.entry(action).or_insert(defaults);
+                }
+                bindings
+            }
+            Err(err) => {
+                warn!("ignoring {}: {}", file, err);
+                defaults
+            }
+        }
+    }
+
+    pub fn save(&self, player: PlayerId) {
+        let file = bindings_file(player);
+        let result = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::save(&file, &contents));
+        if let Err(err) = result {
+            warn!("could not save {}: {}", file, err);
+        }
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.actions.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Makes `binding` trigger only `action`, dropping it from other actions.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        for bindings in self.actions.values_mut() {
+            bindings.retain(|existing| *existing != binding);
+        }
+        self.actions.entry(action).or_default().insert(0, binding);
+    }
+}
+
+/// The first player keeps the file name from before co-op.
+fn bindings_file(player: PlayerId) -> String {
+    match player.0 {
+        0 => "bindings.ron".to_string(),
+        n => format!("bindings-{}.ron", n + 1),
+    }
+}
+
+/// Every player's bindings, indexed by [`PlayerId`].
+#[derive(Clone, Debug)]
+pub struct PlayerBindings([InputBindings; MAX_PLAYERS]);
+
+impl PlayerBindings {
+    pub fn load() -> Self {
+        Self(std::array::from_fn(|i| InputBindings::load(PlayerId(i))))
+    }
+}
+
+impl Default for PlayerBindings {
+    fn default() -> Self {
+        Self(std::array::from_fn(|i| {
+            InputBindings::default_for(PlayerId(i))
+        }))
+    }
+}
+
+impl Index<PlayerId> for PlayerBindings {
+    type Output = InputBindings;
+
+    fn index(&self, player: PlayerId) -> &InputBindings {
+        &self.0[player.0]
+    }
+}
+
+impl IndexMut<PlayerId> for PlayerBindings {
+    fn index_mut(&mut self, player: PlayerId) -> &mut InputBindings {
+        &mut self.0[player.0]
+    }
+}
+
+/// Set to a player's action to bind it to the next input pressed.
+#[derive(Default)]
+pub struct PendingRebind(pub Option<(PlayerId, Action)>);
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum PlayerInputSystem {
+    ReadActions,
+}
+
+/// Reads real devices into [`PlayerInputs`]. Left out when running headless.
+pub struct DeviceInputPlugin;
+
+impl Plugin for DeviceInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ControllerPlugin)
+            .insert_resource(PlayerBindings::load())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                capture_rebind
+                    .after(InputSystem)
+                    .before(PlayerInputSystem::ReadActions),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                read_actions
+                    .label(PlayerInputSystem::ReadActions)
+                    .after(InputSystem),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, read_aim.after(InputSystem));
+    }
+}
+
+/// Current state of every device a [`Binding`] can refer to.
+struct Devices<'a> {
+    keys: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    buttons: &'a Input<GamepadButton>,
+    axes: &'a Axis<GamepadAxis>,
+    gamepad: Option<Gamepad>,
+}
+
+impl Devices<'_> {
+    fn pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keys.pressed(key),
+            Binding::Mouse(button) => self.mouse.pressed(button),
+            Binding::Gamepad(button) => self
+                .gamepad
+                .is_some_and(|gamepad| self.buttons.pressed(GamepadButton(gamepad, button))),
+        }
+    }
+
+    fn just_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keys.just_pressed(key),
+            Binding::Mouse(button) => self.mouse.just_pressed(button),
+            Binding::Gamepad(button) => self
+                .gamepad
+                .is_some_and(|gamepad| self.buttons.just_pressed(GamepadButton(gamepad, button))),
+        }
+    }
+
+    fn action_pressed(&self, bindings: &InputBindings, action: Action) -> bool {
+        bindings
+            .bindings(action)
+            .iter()
+            .any(|binding| self.pressed(*binding))
+    }
+
+    fn action_just_pressed(&self, bindings: &InputBindings, action: Action) -> bool {
+        bindings
+            .bindings(action)
+            .iter()
+            .any(|binding| self.just_pressed(*binding))
+    }
+
+    fn stick(&self, x: GamepadAxisType, y: GamepadAxisType) -> Option<Vec2> {
+        let gamepad = self.gamepad?;
+        let x = self.axes.get(GamepadAxis(gamepad, x))?;
+        let y = self.axes.get(GamepadAxis(gamepad, y))?;
+        Some(Vec2::new(x, y))
+    }
+
+    /// The first input pressed this update, if any.
+    fn first_just_pressed(&self) -> Option<Binding> {
+        if let Some(key) = self.keys.get_just_pressed().next() {
+            return Some(Binding::Key(*key));
+        }
+        if let Some(button) = self.mouse.get_just_pressed().next() {
+            return Some(Binding::Mouse(*button));
+        }
+        self.buttons
+            .get_just_pressed()
+            .next()
+            .map(|button| Binding::Gamepad(button.1))
+    }
+}
+
+/// Reads each player's actions from the keyboard and mouse, which everyone
+/// shares, and from their own gamepad.
+#[allow(clippy::too_many_arguments)]
+fn read_actions(
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<ActiveGamepads>,
+    bindings: Res<PlayerBindings>,
+    pending: Res<PendingRebind>,
+    mut inputs: ResMut<PlayerInputs>,
+    mut stick_steps: Local<[i32; MAX_PLAYERS]>,
+) {
+    for i in 0..MAX_PLAYERS {
+        let player = PlayerId(i);
+        let devices = Devices {
+            keys: &keys,
+            mouse: &mouse,
+            buttons: &buttons,
+            axes: &axes,
+            gamepad: gamepads.get(player),
+        };
+        let bindings = &bindings[player];
+        let input = &mut inputs[player];
+        let pressed = |action| devices.action_pressed(bindings, action);
+
+        let stick = devices.stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+        // the stick moves the menu focus once per push, not once per update
+        let stick_step = stick.map_or(0, |stick| {
+            if stick.y > MENU_STICK_THRESHOLD {
+                -1
+            } else if stick.y < -MENU_STICK_THRESHOLD {
+                1
+            } else {
+                0
+            }
+        });
+        let stick_pushed = stick_step != stick_steps[i];
+        stick_steps[i] = stick_step;
+
+        let stick = stick.map_or(Vec2::ZERO, apply_move_deadzone);
+        input.movement = if stick != Vec2::ZERO {
+            stick
+        } else {
+            Vec2::new(
+                (-(pressed(Action::MoveLeft) as i8) + pressed(Action::MoveRight) as i8) as f32,
+                (-(pressed(Action::MoveDown) as i8) + pressed(Action::MoveUp) as i8) as f32,
+            )
+        };
+        input.reflect = pressed(Action::Reflect);
+        // the input that finishes a rebind shouldn't also trigger its old action
+        if pending.0.is_some() || pending.is_changed() {
+            continue;
+        }
+        // kept until a tick uses it, since an update can run no ticks at all
+        input.dash |= devices.action_just_pressed(bindings, Action::Dash);
+        input.pause = devices.action_just_pressed(bindings, Action::Pause);
+        let just_pressed = |bindings: &[Binding]| {
+            bindings
+                .iter()
+                .any(|binding| devices.just_pressed(*binding))
+        };
+        input.confirm = just_pressed(&MENU_CONFIRM);
+        let stick_step = if stick_pushed { stick_step } else { 0 };
+        input.menu_step =
+            (just_pressed(&MENU_DOWN) as i32 - just_pressed(&MENU_UP) as i32 + stick_step).signum();
+    }
+}
+
+/// Ignores small stick movements and rescales the rest so that movement
+/// starts from zero at the edge of the deadzone.
+fn apply_move_deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length <= MOVE_DEADZONE {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - MOVE_DEADZONE) / (1.0 - MOVE_DEADZONE)).min(1.0);
+    stick / length * scaled
+}
+
+fn capture_rebind(
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<ActiveGamepads>,
+    mut bindings: ResMut<PlayerBindings>,
+    mut pending: ResMut<PendingRebind>,
+) {
+    let (player, action) = match pending.0 {
+        Some(pending) => pending,
+        None => return,
+    };
+    let devices = Devices {
+        keys: &keys,
+        mouse: &mouse,
+        buttons: &buttons,
+        axes: &axes,
+        gamepad: gamepads.get(player),
+    };
+    if let Some(binding) = devices.first_just_pressed() {
+        bindings[player].rebind(action, binding);
+        bindings[player].save(player);
+        pending.0 = None;
+    }
+}
+
+/// Aims each player with their right stick. The first player aims with the
+/// mouse cursor while their stick is at rest.
+fn read_aim(
+    windows: Res<Windows>,
+    gamepads: Res<ActiveGamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    q_player: Query<(&PlayerId, &GlobalTransform), With<Player>>,
+    mut inputs: ResMut<PlayerInputs>,
+) {
+    for i in 0..MAX_PLAYERS {
+        let player = PlayerId(i);
+        let stick = gamepads.get(player).and_then(|gamepad| {
+            let x = axes.get(GamepadAxis(gamepad, GamepadAxisType::RightStickX))?;
+            let y = axes.get(GamepadAxis(gamepad, GamepadAxisType::RightStickY))?;
+            let stick = Vec2::new(x, y);
+            (stick.length() > STICK_DEADZONE).then_some(stick)
+        });
+        inputs[player].aim = stick.or_else(|| {
+            if player.0 > 0 {
+                return None;
+            }
+            let (_, transform) = q_player.iter().find(|(id, _)| **id == player)?;
+            let cursor = cursor_world_position(&windows, &q_camera)?;
+            Some(cursor - transform.translation.truncate())
+        });
+    }
+}
+
+fn cursor_world_position(
+    windows: &Windows,
+    q_camera: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = q_camera.get_single().ok()?;
+    let window = windows.get(camera.window)?;
+    let cursor = window.cursor_position()?;
+    let window_size = Vec2::new(window.width(), window.height());
+    let ndc = (cursor / window_size) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix.inverse();
+    Some(ndc_to_world.project_point3(ndc.extend(-1.0)).truncate())
+}
+
+fn clear_pressed(mut inputs: ResMut<PlayerInputs>, state: Res<State<GameState>>) {
+    let playing = *state.current() == GameState::Playing;
+    for input in inputs.0.iter_mut() {
+        input.pause = false;
+        input.confirm = false;
+        input.menu_step = 0;
+        if !playing {
+            input.dash = false;
+        }
+    }
+}
@@ -1,32 +1,81 @@
 mod alarm;
+mod cleanup;
 mod clock;
 mod components;
+mod config;
+mod controller;
+mod difficulty;
+mod endless;
+pub mod events;
 mod game_over;
 mod game_win;
 mod grandfather;
+mod highscore;
+mod input;
 mod loading;
 mod menu;
+mod pattern;
+mod pause;
+mod physics;
 mod player;
 mod reflector;
+mod replay;
+mod rng;
+mod simulation;
+mod stats;
+mod storage;
+mod wave;
 
 use alarm::AlarmPlugin;
-use bevy::prelude::{App, Plugin};
-use bevy_rapier2d::physics::{NoUserData, RapierPhysicsPlugin};
+use bevy::prelude::{App, Handle, Plugin, TransformPlugin, WindowDescriptor};
+use cleanup::CleanupPlugin;
 use clock::ClockPlugin;
+use config::ConfigPlugin;
+use endless::EndlessPlugin;
+use events::EventsPlugin;
 use game_over::GameOverPlugin;
 use game_win::GameWinPlugin;
 use grandfather::GrandfatherPlugin;
-use loading::LoadingPlugin;
+use highscore::{save_high_scores, HighScorePlugin};
+use input::{DeviceInputPlugin, PlayerInputPlugin};
+use loading::{FontAssets, LoadingPlugin, TextureAssets};
 use menu::MenuPlugin;
+use pause::{pause_on_focus_loss, PausePlugin};
+use physics::PhysicsPlugin;
 use player::PlayerPlugin;
+use replay::{save_replay, ReplayPlugin};
+use rng::RngPlugin;
+use simulation::{SimulationPlugin, SimulationTime};
+use stats::StatsPlugin;
+use wave::WavePlugin;
+
+pub use alarm::AlarmKind;
+pub use clock::Clock;
+pub use components::{Player, PlayerId, MAX_PLAYERS};
+pub use config::{parse_config, ConfigAsset, GameConfig};
+pub use difficulty::Difficulty;
+pub use endless::GameMode;
+pub use grandfather::Grandfather;
+pub use highscore::{HighScores, RunRecord};
+pub use input::{Action, Binding, InputBindings, PlayerInput, PlayerInputs};
+pub use pattern::{Pattern, PatternLibrary, PatternStep};
+pub use player::PlayerCount;
+pub use replay::{LastReplay, Replay, ReplayFrame, ReplayPlayback};
+pub use rng::{daily_seed, GameRng, SeedOverride};
+pub use stats::RunStats;
+pub use wave::WaveTimeline;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
-enum GameState {
+pub enum GameState {
     Loading,
     Menu,
     Playing,
     GameOver,
     GameWin,
+    /// Pushed on top of `Playing`, which carries on where it left off once
+    /// this is popped.
+    Paused,
+    HighScores,
 }
 
 pub struct GamePlugin;
@@ -34,13 +83,72 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_state(GameState::Loading)
-            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+            .add_plugin(ConfigPlugin)
             .add_plugin(LoadingPlugin)
-            .add_plugin(MenuPlugin)
-            .add_plugin(PlayerPlugin)
-            .add_plugin(GrandfatherPlugin)
-            .add_plugin(ClockPlugin)
-            .add_plugin(GameOverPlugin)
-            .add_plugin(GameWinPlugin);
+            .add_plugin(DeviceInputPlugin)
+            .insert_resource(HighScores::load())
+            .add_system(pause_on_focus_loss)
+            .add_system(save_high_scores)
+            .add_system(save_replay);
+        add_gameplay_plugins(app);
     }
 }
+
+/// Runs the game without a window, assets or real input, starting straight in
+/// [`GameState::Playing`]. Every update runs exactly one gameplay tick and
+/// every run starts from `seed`, so a run is the same each time. Input is
+/// driven by writing to the [`PlayerInputs`] resource.
+pub struct HeadlessPlugin {
+    pub config: GameConfig,
+    pub waves: WaveTimeline,
+    pub patterns: PatternLibrary,
+    pub seed: u64,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(GameState::Playing)
+            .add_plugin(TransformPlugin)
+            .insert_resource(self.config.clone())
+            .insert_resource(self.waves.clone())
+            .insert_resource(self.patterns.clone())
+            .insert_resource(FontAssets {
+                roboto: Handle::default(),
+            })
+            .insert_resource(TextureAssets {
+                title_texture: Handle::default(),
+                button_texture: Handle::default(),
+                stage_texture: Handle::default(),
+                player_texture_64: Handle::default(),
+                reflector_texture: Handle::default(),
+                alarm_texture: Handle::default(),
+                grandfather_moon: Handle::default(),
+                grandfather_sun: Handle::default(),
+            })
+            .init_resource::<WindowDescriptor>()
+            .insert_resource(SimulationTime::lockstep())
+            .insert_resource(SeedOverride(Some(self.seed)));
+        add_gameplay_plugins(app);
+    }
+}
+
+fn add_gameplay_plugins(app: &mut App) {
+    app.add_plugin(SimulationPlugin)
+        .add_plugin(PhysicsPlugin)
+        .add_plugin(RngPlugin)
+        .add_plugin(CleanupPlugin)
+        .add_plugin(PlayerInputPlugin)
+        .add_plugin(EventsPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(PlayerPlugin)
+        .add_plugin(GrandfatherPlugin)
+        .add_plugin(WavePlugin)
+        .add_plugin(EndlessPlugin)
+        .add_plugin(ClockPlugin)
+        .add_plugin(StatsPlugin)
+        .add_plugin(GameOverPlugin)
+        .add_plugin(GameWinPlugin)
+        .add_plugin(HighScorePlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(PausePlugin);
+}
@@ -1,3 +1,7 @@
+use crate::cleanup::StateScoped;
+use crate::config::{ConfigErrors, GameConfig};
+use crate::pattern::PatternLibrary;
+use crate::wave::WaveTimeline;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_asset_loader::{AssetCollection, AssetLoader};
@@ -9,11 +13,21 @@ impl Plugin for LoadingPlugin {
         AssetLoader::new(GameState::Loading)
             .with_collection::<FontAssets>()
             .with_collection::<TextureAssets>()
+            .with_collection::<ConfigAssets>()
             .continue_to_state(GameState::Menu)
             .build(app);
+
+        app.add_startup_system(spawn_ui_camera)
+            .add_system_set(
+                SystemSet::on_update(GameState::Loading).with_system(show_config_errors),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(insert_config));
     }
 }
 
+#[derive(Component)]
+struct ConfigErrorsUI;
+
 #[derive(AssetCollection)]
 pub struct FontAssets {
     #[asset(path = "fonts/ferrum.otf")]
@@ -26,6 +40,7 @@ pub struct TextureAssets {
     pub title_texture: Handle<Image>,
     #[asset(path = "textures/button.png")]
     pub button_texture: Handle<Image>,
+    #[allow(dead_code)]
     #[asset(path = "textures/stage.png")]
     pub stage_texture: Handle<Image>,
     #[asset(path = "textures/player-64x64.png")]
@@ -39,3 +54,83 @@ pub struct TextureAssets {
     #[asset(path = "textures/grandfather_sun.png")]
     pub grandfather_sun: Handle<Image>,
 }
+
+#[derive(AssetCollection)]
+pub struct ConfigAssets {
+    #[asset(path = "config/game.config.ron")]
+    pub game: Handle<GameConfig>,
+    #[asset(path = "config/game.waves.ron")]
+    pub waves: Handle<WaveTimeline>,
+    #[asset(path = "config/game.patterns.ron")]
+    pub patterns: Handle<PatternLibrary>,
+}
+
+/// The one camera every screen's UI is drawn with.
+fn spawn_ui_camera(mut commands: Commands) {
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+fn insert_config(
+    mut commands: Commands,
+    config_assets: Res<ConfigAssets>,
+    configs: Res<Assets<GameConfig>>,
+    timelines: Res<Assets<WaveTimeline>>,
+    libraries: Res<Assets<PatternLibrary>>,
+) {
+    let config = configs
+        .get(config_assets.game.clone())
+        .expect("game config was not loaded");
+    commands.insert_resource(config.clone());
+    let timeline = timelines
+        .get(config_assets.waves.clone())
+        .expect("wave timeline was not loaded");
+    commands.insert_resource(timeline.clone());
+    let library = libraries
+        .get(config_assets.patterns.clone())
+        .expect("pattern library was not loaded");
+    commands.insert_resource(library.clone());
+}
+
+fn show_config_errors(
+    mut commands: Commands,
+    errors: Res<ConfigErrors>,
+    font_assets: Option<Res<FontAssets>>,
+    q_text: Query<Entity, With<ConfigErrorsUI>>,
+) {
+    let messages = errors.messages();
+    if messages.is_empty() || !q_text.is_empty() {
+        return;
+    }
+    let font_assets = match font_assets {
+        Some(font_assets) => font_assets,
+        None => return,
+    };
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(30.0),
+                    left: Val::Px(30.0),
+                    ..Default::default()
+                },
+                max_size: Size::new(Val::Px(740.0), Val::Undefined),
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: format!("Invalid game config:\n{}", messages.join("\n")),
+                    style: TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 20.0,
+                        color: Color::rgb(0.9, 0.2, 0.2),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Loading))
+        .insert(ConfigErrorsUI);
+}
@@ -2,19 +2,50 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use bevy::prelude::{App, ClearColor, Color, DefaultPlugins, WindowDescriptor};
-use wasting_time::GamePlugin;
+use wasting_time::{GamePlugin, Replay, ReplayPlayback, SeedOverride};
 
 fn main() {
-    App::new()
-        .insert_resource(WindowDescriptor {
-            title: "Wasting Time".to_string(),
-            width: 800.,
-            height: 600.,
-            resizable: false,
-            ..Default::default()
-        })
-        .insert_resource(ClearColor(Color::rgb(0.5, 0.5, 0.5)))
-        .add_plugins(DefaultPlugins)
-        .add_plugin(GamePlugin)
-        .run();
+    let mut app = App::new();
+    app.insert_resource(WindowDescriptor {
+        title: "Wasting Time".to_string(),
+        width: 800.,
+        height: 600.,
+        resizable: false,
+        ..Default::default()
+    })
+    .insert_resource(ClearColor(Color::rgb(0.5, 0.5, 0.5)))
+    .add_plugins(DefaultPlugins)
+    .add_plugin(GamePlugin);
+
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    // `--seed <number>` plays every run with that seed instead of the day's
+    if let Some(seed) = value_of("--seed") {
+        match seed.parse() {
+            Ok(seed) => {
+                app.insert_resource(SeedOverride(Some(seed)));
+            }
+            Err(err) => eprintln!("could not use seed {}: {}", seed, err),
+        }
+    }
+
+    // `--replay <file>` plays a recorded run back instead of starting at the menu
+    if let Some(path) = value_of("--replay") {
+        match std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| Replay::from_ron(&contents))
+        {
+            Ok(replay) => {
+                app.insert_resource(ReplayPlayback::new(replay));
+            }
+            Err(err) => eprintln!("could not load replay {}: {}", path, err),
+        }
+    }
+
+    app.run();
 }
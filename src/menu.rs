@@ -1,26 +1,74 @@
 use bevy::prelude::*;
 
+use crate::cleanup::StateScoped;
+use crate::components::MAX_PLAYERS;
+use crate::difficulty::Difficulty;
+use crate::endless::GameMode;
+use crate::input::PlayerInputs;
 use crate::loading::{FontAssets, TextureAssets};
+use crate::player::PlayerCount;
 use crate::GameState;
 
-pub struct MenuPlugin;
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum MenuButton {
+    Play,
+    HighScores,
+    /// Steps through the difficulties.
+    Difficulty,
+    /// Switches between the game modes.
+    Mode,
+    /// Switches between playing alone and local co-op.
+    Players,
+}
+
+impl MenuButton {
+    /// Top to bottom, the order the focus moves through them.
+    const ORDER: [MenuButton; 5] = [
+        MenuButton::Play,
+        MenuButton::HighScores,
+        MenuButton::Difficulty,
+        MenuButton::Mode,
+        MenuButton::Players,
+    ];
+}
+
+/// The button a confirm press activates, as an index into
+/// [`MenuButton::ORDER`]. Back on Play each time the menu opens.
+#[derive(Default)]
+struct MenuFocus(usize);
 
+const FOCUSED_COLOR: Color = Color::rgb(1.0, 0.85, 0.4);
+
+/// Shows the current choice on the option button it belongs to.
 #[derive(Component)]
-struct TitleImage;
+struct OptionLabel(MenuButton);
+
+pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(GameState::Menu).with_system(setup_menu))
-            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(click_play_button));
+        app.init_resource::<Difficulty>()
+            .init_resource::<MenuFocus>()
+            .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(setup_menu))
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu)
+                    .with_system(click_menu_button)
+                    .with_system(highlight_focused_button)
+                    .with_system(update_option_labels),
+            );
     }
 }
 
 fn setup_menu(
     mut commands: Commands,
+    mut focus: ResMut<MenuFocus>,
     font_assets: Res<FontAssets>,
     texture_assets: Res<TextureAssets>,
+    difficulty: Res<Difficulty>,
+    mode: Res<GameMode>,
+    player_count: Res<PlayerCount>,
 ) {
-    commands.spawn_bundle(UiCameraBundle::default());
+    focus.0 = 0;
     commands
         .spawn_bundle(ImageBundle {
             style: Style {
@@ -35,22 +83,24 @@ fn setup_menu(
             image: texture_assets.title_texture.clone().into(),
             ..Default::default()
         })
-        .insert(TitleImage);
+        .insert(StateScoped(GameState::Menu));
     commands
         .spawn_bundle(ButtonBundle {
             image: texture_assets.button_texture.clone().into(),
             style: Style {
                 position_type: PositionType::Absolute,
                 position: Rect {
-                    top: Val::Px(330.0),
+                    top: Val::Px(295.0),
                     left: Val::Px(350.0),
                     ..Default::default()
                 },
-                size: Size::new(Val::Px(100.0), Val::Px(60.0)),
+                size: Size::new(Val::Px(100.0), Val::Px(55.0)),
                 ..Default::default()
             },
             ..Default::default()
         })
+        .insert(StateScoped(GameState::Menu))
+        .insert(MenuButton::Play)
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 style: Style {
@@ -77,29 +127,200 @@ fn setup_menu(
             });
         });
 
+    commands
+        .spawn_bundle(ButtonBundle {
+            image: texture_assets.button_texture.clone().into(),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(355.0),
+                    left: Val::Px(300.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(200.0), Val::Px(55.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Menu))
+        .insert(MenuButton::HighScores)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        top: Val::Px(12.0),
+                        left: Val::Px(20.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text {
+                    sections: vec![TextSection {
+                        value: "High Scores".to_string(),
+                        style: TextStyle {
+                            font: font_assets.roboto.clone(),
+                            font_size: 40.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    }],
+                    alignment: Default::default(),
+                },
+                ..Default::default()
+            });
+        });
+
+    let options = [
+        (415.0, MenuButton::Difficulty),
+        (475.0, MenuButton::Mode),
+        (535.0, MenuButton::Players),
+    ];
+    for (top, button) in options {
+        commands
+            .spawn_bundle(ButtonBundle {
+                image: texture_assets.button_texture.clone().into(),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        top: Val::Px(top),
+                        left: Val::Px(250.0),
+                        ..Default::default()
+                    },
+                    size: Size::new(Val::Px(300.0), Val::Px(55.0)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(StateScoped(GameState::Menu))
+            .insert(button)
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(TextBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: Rect {
+                                top: Val::Px(12.0),
+                                left: Val::Px(20.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: option_label(button, *difficulty, *mode, &player_count),
+                                style: TextStyle {
+                                    font: font_assets.roboto.clone(),
+                                    font_size: 40.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                            }],
+                            alignment: Default::default(),
+                        },
+                        ..Default::default()
+                    })
+                    .insert(OptionLabel(button));
+            });
+    }
+
     // commands.spawn_bundle(ImageBundle {
     //     image: texture_assets.stage_texture.clone().into(),
     //     ..Default::default()
     // });
 }
 
-type ButtonInteraction<'a> = (Entity, &'a Interaction, &'a Children);
+fn option_label(
+    button: MenuButton,
+    difficulty: Difficulty,
+    mode: GameMode,
+    player_count: &PlayerCount,
+) -> String {
+    match button {
+        MenuButton::Difficulty => format!("Difficulty: {}", difficulty),
+        MenuButton::Mode => format!("Mode: {}", mode),
+        MenuButton::Players => format!("Players: {}", player_count.0),
+        MenuButton::Play | MenuButton::HighScores => String::new(),
+    }
+}
 
-fn click_play_button(
-    mut commands: Commands,
+// Only reacts to the press itself, so holding the button down does not spin
+// through every option. A confirm press activates the focused button, which
+// the D-pad, arrow keys and stick move up and down the menu.
+fn click_menu_button(
     mut state: ResMut<State<GameState>>,
-    q_interaction: Query<ButtonInteraction, (Changed<Interaction>, With<Button>)>,
-    q_text: Query<Entity, With<Text>>,
-    q_image: Query<Entity, With<TitleImage>>,
+    mut difficulty: ResMut<Difficulty>,
+    mut mode: ResMut<GameMode>,
+    mut player_count: ResMut<PlayerCount>,
+    mut focus: ResMut<MenuFocus>,
+    mut inputs: ResMut<PlayerInputs>,
+    q_interaction: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
 ) {
-    let image = q_image.single();
-    for (button, interaction, children) in q_interaction.iter() {
-        let text = q_text.get(children[0]).unwrap();
-        if *interaction == Interaction::Clicked {
-            commands.entity(button).despawn();
-            commands.entity(text).despawn();
-            commands.entity(image).despawn();
-            state.set(GameState::Playing).unwrap();
+    let step: i32 = inputs.iter().map(|input| input.menu_step).sum();
+    if step != 0 {
+        let buttons = MenuButton::ORDER.len() as i32;
+        focus.0 = (focus.0 as i32 + step).rem_euclid(buttons) as usize;
+    }
+
+    let clicked = q_interaction
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| *button);
+    let pressed = match clicked {
+        Some(button) => {
+            focus.0 = MenuButton::ORDER.iter().position(|b| *b == button).unwrap();
+            button
+        }
+        None if inputs.confirm() => {
+            inputs.consume_menu_presses();
+            MenuButton::ORDER[focus.0]
+        }
+        None => return,
+    };
+    let next = match pressed {
+        MenuButton::Play => GameState::Playing,
+        MenuButton::HighScores => GameState::HighScores,
+        MenuButton::Difficulty => {
+            *difficulty = difficulty.next();
+            return;
+        }
+        MenuButton::Mode => {
+            *mode = mode.next();
+            return;
+        }
+        MenuButton::Players => {
+            player_count.0 = player_count.0 % MAX_PLAYERS + 1;
+            return;
         }
+    };
+    state.set(next).ok();
+}
+
+fn highlight_focused_button(
+    focus: Res<MenuFocus>,
+    mut q_button: Query<(&MenuButton, &mut UiColor)>,
+) {
+    let focused = MenuButton::ORDER[focus.0];
+    for (button, mut color) in q_button.iter_mut() {
+        let wanted = if *button == focused {
+            FOCUSED_COLOR
+        } else {
+            Color::WHITE
+        };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+}
+
+fn update_option_labels(
+    difficulty: Res<Difficulty>,
+    mode: Res<GameMode>,
+    player_count: Res<PlayerCount>,
+    mut q_label: Query<(&mut Text, &OptionLabel)>,
+) {
+    if !difficulty.is_changed() && !mode.is_changed() && !player_count.is_changed() {
+        return;
+    }
+    for (mut text, label) in q_label.iter_mut() {
+        text.sections[0].value = option_label(label.0, *difficulty, *mode, &player_count);
     }
 }
@@ -0,0 +1,357 @@
+//! Bullet patterns: attacks written out as data, which a grandfather can throw
+//! in place of its wave's alarms. A pattern is a list of steps that expands
+//! into timed shots, fired off one tick at a time as the pattern runs.
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f32::consts::TAU;
+
+use crate::alarm::{AlarmKind, SpawnAlarmEvent};
+use crate::config::{check_positive, ConfigAsset};
+
+/// The most shots one pattern may expand to, so a typo in a repeat count
+/// cannot flood the stage.
+const MAX_SHOTS: u64 = 500;
+
+/// Every pattern the grandfathers can use, by name.
+#[derive(Serialize, Deserialize, TypeUuid, Clone, Debug, Default)]
+#[uuid = "0b7e5d21-93c4-4a8f-b6d2-5e1f8a3c7d64"]
+pub struct PatternLibrary {
+    pub patterns: BTreeMap<String, Pattern>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Pattern {
+    /// Pixels from where the grandfather throws from to where its shots
+    /// appear, enough to clear its own collider.
+    pub radius: f32,
+    pub steps: Vec<PatternStep>,
+}
+
+/// One step of a pattern, played after the one before it has finished.
+/// Angles are in degrees from the direction of the nearest player when the
+/// pattern starts, and speeds are multiples of the configured alarm speed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum PatternStep {
+    /// Shots all at once, evenly spaced all the way round.
+    Ring {
+        count: u32,
+        speed: f32,
+        kind: AlarmKind,
+    },
+    /// Shots all at once, spread evenly over `spread` degrees.
+    Fan {
+        count: u32,
+        spread: f32,
+        speed: f32,
+        kind: AlarmKind,
+    },
+    /// Shots `interval` seconds apart, each turned `turn` degrees further than
+    /// the last.
+    Spiral {
+        count: u32,
+        turn: f32,
+        interval: f32,
+        speed: f32,
+        kind: AlarmKind,
+    },
+    /// Shots `interval` seconds apart, each aimed at wherever the nearest
+    /// player is when it is fired.
+    Burst {
+        count: u32,
+        interval: f32,
+        speed: f32,
+        kind: AlarmKind,
+    },
+    /// Seconds to wait before the next step.
+    Delay(f32),
+    /// Degrees every later shot is turned by.
+    Rotate(f32),
+    /// Plays `steps` over `times` times.
+    Repeat { times: u32, steps: Vec<PatternStep> },
+}
+
+/// A single shot of an expanded pattern.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shot {
+    /// Seconds after the pattern starts.
+    pub at: f32,
+    /// Radians from the direction the shot is aimed in.
+    pub angle: f32,
+    /// Aimed at the player when fired, rather than when the pattern started.
+    pub tracks_player: bool,
+    pub speed: f32,
+    pub kind: AlarmKind,
+}
+
+impl Pattern {
+    /// Every shot of the pattern, in the order they are fired.
+    pub fn shots(&self) -> Vec<Shot> {
+        let mut shots = Vec::new();
+        expand(&self.steps, &mut 0.0, &mut 0.0, &mut shots);
+        shots
+    }
+}
+
+/// Appends the shots of `steps`, moving the clock `at` and the running
+/// `rotation` on as it goes.
+fn expand(steps: &[PatternStep], at: &mut f32, rotation: &mut f32, shots: &mut Vec<Shot>) {
+    for step in steps {
+        match *step {
+            PatternStep::Ring { count, speed, kind } => {
+                for i in 0..count {
+                    shots.push(Shot {
+                        at: *at,
+                        angle: *rotation + i as f32 * TAU / count as f32,
+                        tracks_player: false,
+                        speed,
+                        kind,
+                    });
+                }
+            }
+            PatternStep::Fan {
+                count,
+                spread,
+                speed,
+                kind,
+            } => {
+                let gap = if count > 1 {
+                    spread / (count - 1) as f32
+                } else {
+                    0.0
+                };
+                for i in 0..count {
+                    let angle = -spread / 2.0 + i as f32 * gap;
+                    shots.push(Shot {
+                        at: *at,
+                        angle: *rotation + angle.to_radians(),
+                        tracks_player: false,
+                        speed,
+                        kind,
+                    });
+                }
+            }
+            PatternStep::Spiral {
+                count,
+                turn,
+                interval,
+                speed,
+                kind,
+            } => {
+                for _ in 0..count {
+                    shots.push(Shot {
+                        at: *at,
+                        angle: *rotation,
+                        tracks_player: false,
+                        speed,
+                        kind,
+                    });
+                    *at += interval;
+                    *rotation += turn.to_radians();
+                }
+            }
+            PatternStep::Burst {
+                count,
+                interval,
+                speed,
+                kind,
+            } => {
+                for _ in 0..count {
+                    shots.push(Shot {
+                        at: *at,
+                        angle: *rotation,
+                        tracks_player: true,
+                        speed,
+                        kind,
+                    });
+                    *at += interval;
+                }
+            }
+            PatternStep::Delay(seconds) => *at += seconds,
+            PatternStep::Rotate(degrees) => *rotation += degrees.to_radians(),
+            PatternStep::Repeat { times, ref steps } => {
+                for _ in 0..times {
+                    expand(steps, at, rotation, shots);
+                }
+            }
+        }
+    }
+}
+
+/// How many shots `steps` expand to, without expanding them.
+fn shot_count(steps: &[PatternStep]) -> u64 {
+    steps
+        .iter()
+        .map(|step| match step {
+            PatternStep::Ring { count, .. }
+            | PatternStep::Fan { count, .. }
+            | PatternStep::Spiral { count, .. }
+            | PatternStep::Burst { count, .. } => *count as u64,
+            PatternStep::Delay(_) | PatternStep::Rotate(_) => 0,
+            PatternStep::Repeat { times, steps } => {
+                (*times as u64).saturating_mul(shot_count(steps))
+            }
+        })
+        .fold(0, u64::saturating_add)
+}
+
+fn validate_steps(errors: &mut Vec<String>, path: &str, steps: &[PatternStep]) {
+    let check_count = |errors: &mut Vec<String>, name: &str, count: u32| {
+        if count == 0 {
+            errors.push(format!("{} must be at least 1", name));
+        }
+    };
+    let check_interval = |errors: &mut Vec<String>, name: &str, interval: f32| {
+        if !interval.is_finite() || interval < 0.0 {
+            errors.push(format!("{} must not be negative, got {}", name, interval));
+        }
+    };
+    for (i, step) in steps.iter().enumerate() {
+        let path = format!("{}[{}]", path, i);
+        match step {
+            PatternStep::Ring { count, speed, .. } => {
+                check_count(errors, &format!("{}.count", path), *count);
+                check_positive(errors, &format!("{}.speed", path), *speed);
+            }
+            PatternStep::Fan {
+                count,
+                spread,
+                speed,
+                ..
+            } => {
+                check_count(errors, &format!("{}.count", path), *count);
+                check_interval(errors, &format!("{}.spread", path), *spread);
+                check_positive(errors, &format!("{}.speed", path), *speed);
+            }
+            PatternStep::Spiral {
+                count,
+                turn,
+                interval,
+                speed,
+                ..
+            } => {
+                check_count(errors, &format!("{}.count", path), *count);
+                if !turn.is_finite() {
+                    errors.push(format!("{}.turn must be a number, got {}", path, turn));
+                }
+                check_interval(errors, &format!("{}.interval", path), *interval);
+                check_positive(errors, &format!("{}.speed", path), *speed);
+            }
+            PatternStep::Burst {
+                count,
+                interval,
+                speed,
+                ..
+            } => {
+                check_count(errors, &format!("{}.count", path), *count);
+                check_interval(errors, &format!("{}.interval", path), *interval);
+                check_positive(errors, &format!("{}.speed", path), *speed);
+            }
+            PatternStep::Delay(seconds) => check_interval(errors, &path, *seconds),
+            PatternStep::Rotate(degrees) => {
+                if !degrees.is_finite() {
+                    errors.push(format!("{} must be a number, got {}", path, degrees));
+                }
+            }
+            PatternStep::Repeat { times, steps } => {
+                check_count(errors, &format!("{}.times", path), *times);
+                validate_steps(errors, &format!("{}.steps", path), steps);
+            }
+        }
+    }
+}
+
+impl ConfigAsset for PatternLibrary {
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        for (name, pattern) in self.patterns.iter() {
+            let path = format!("patterns[{:?}]", name);
+            check_positive(&mut errors, &format!("{}.radius", path), pattern.radius);
+            validate_steps(&mut errors, &format!("{}.steps", path), &pattern.steps);
+            let count = shot_count(&pattern.steps);
+            if count == 0 {
+                errors.push(format!("{} never fires a shot", path));
+            } else if count > MAX_SHOTS {
+                errors.push(format!(
+                    "{} fires {} shots, more than the limit of {}",
+                    path, count, MAX_SHOTS
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// A pattern part way through being thrown.
+pub struct PatternRun {
+    shots: Vec<Shot>,
+    radius: f32,
+    /// Towards the nearest player when the pattern started.
+    aim: Vec2,
+    /// How many of `shots` have been fired.
+    fired: usize,
+    elapsed: f32,
+}
+
+impl PatternRun {
+    pub fn new(pattern: &Pattern, origin: Vec2, player: Option<Vec2>) -> Self {
+        Self {
+            shots: pattern.shots(),
+            radius: pattern.radius,
+            aim: aim(origin, player),
+            fired: 0,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.fired == self.shots.len()
+    }
+
+    /// Fires the shots that are due from `origin`, then moves the pattern on
+    /// by `delta` seconds. Shots at the very start go out on the first call.
+    pub fn advance(
+        &mut self,
+        delta: f32,
+        origin: Vec2,
+        player: Option<Vec2>,
+        alarm_speed: f32,
+    ) -> Vec<SpawnAlarmEvent> {
+        let mut events = Vec::new();
+        while let Some(shot) = self.shots.get(self.fired) {
+            if shot.at > self.elapsed {
+                break;
+            }
+            let aim = if shot.tracks_player {
+                aim(origin, player)
+            } else {
+                self.aim
+            };
+            let direction = aim * shot.angle.cos() + aim.perp() * shot.angle.sin();
+            events.push(SpawnAlarmEvent {
+                position: (origin + direction * self.radius).extend(0.0),
+                velocity: direction * shot.speed * alarm_speed,
+                kind: shot.kind,
+                reflected: false,
+            });
+            self.fired += 1;
+        }
+        self.elapsed += delta;
+        events
+    }
+}
+
+/// The direction from `origin` to the player, or straight down without one.
+fn aim(origin: Vec2, player: Option<Vec2>) -> Vec2 {
+    player
+        .map(|player| (player - origin).normalize_or_zero())
+        .filter(|aim| *aim != Vec2::ZERO)
+        .unwrap_or(-Vec2::Y)
+}
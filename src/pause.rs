@@ -0,0 +1,360 @@
+use crate::cleanup::StateScoped;
+use crate::components::{PlayerId, MAX_PLAYERS};
+use crate::input::{Action, Binding, InputBindings, PendingRebind, PlayerBindings, PlayerInputs};
+use crate::loading::{FontAssets, TextureAssets};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+
+const REBINDABLE: [(Action, &str); 7] = [
+    (Action::MoveUp, "Up"),
+    (Action::MoveDown, "Down"),
+    (Action::MoveLeft, "Left"),
+    (Action::MoveRight, "Right"),
+    (Action::Dash, "Dash"),
+    (Action::Reflect, "Reflect"),
+    (Action::Pause, "Pause"),
+];
+
+#[derive(Component, Clone, Copy)]
+enum PauseButton {
+    Resume,
+    Restart,
+    Settings,
+    QuitToMenu,
+}
+
+#[derive(Component)]
+struct SettingsUI;
+
+#[derive(Component)]
+struct RebindButton(Action);
+
+/// Switches which player the rebind buttons are for.
+#[derive(Component)]
+struct RebindPlayerButton;
+
+/// The player whose bindings the settings show.
+#[derive(Default)]
+struct RebindPlayer(PlayerId);
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RebindPlayer>()
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(pause_game))
+            .add_system_set(SystemSet::on_enter(GameState::Paused).with_system(spawn_pause_menu))
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(resume_game)
+                    .with_system(click_pause_button)
+                    .with_system(click_rebind_button)
+                    .with_system(click_rebind_player_button)
+                    .with_system(update_rebind_labels),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(cancel_rebind));
+    }
+}
+
+// Both toggles consume the press: the state change re-runs the stage within
+// the same update, and the other side would otherwise see it again.
+fn pause_game(mut inputs: ResMut<PlayerInputs>, mut state: ResMut<State<GameState>>) {
+    if inputs.pause() {
+        inputs.consume_menu_presses();
+        // a run that just ended has already queued its own transition
+        state.push(GameState::Paused).ok();
+    }
+}
+
+/// Pauses when the window is left in the middle of a run.
+pub fn pause_on_focus_loss(
+    mut ev_focused: EventReader<WindowFocused>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let lost_focus = ev_focused.iter().any(|event| !event.focused);
+    if lost_focus && *state.current() == GameState::Playing {
+        state.push(GameState::Paused).ok();
+    }
+}
+
+fn resume_game(
+    mut inputs: ResMut<PlayerInputs>,
+    pending: Res<PendingRebind>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if pending.0.is_none() && (inputs.pause() || inputs.confirm()) {
+        inputs.consume_menu_presses();
+        state.pop().ok();
+    }
+}
+
+// A click can land in the same update as a pause or confirm press, which
+// resume_game acts on; whichever transition is queued first wins.
+fn click_pause_button(
+    mut commands: Commands,
+    mut state: ResMut<State<GameState>>,
+    font_assets: Res<FontAssets>,
+    texture_assets: Res<TextureAssets>,
+    q_interaction: Query<(&Interaction, &PauseButton), Changed<Interaction>>,
+    q_settings: Query<Entity, With<SettingsUI>>,
+) {
+    for (interaction, button) in q_interaction.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            PauseButton::Resume => {
+                state.pop().ok();
+            }
+            PauseButton::Restart => {
+                state.replace(GameState::Playing).ok();
+            }
+            PauseButton::Settings => {
+                if q_settings.is_empty() {
+                    spawn_settings(&mut commands, &font_assets, &texture_assets);
+                } else {
+                    for settings in q_settings.iter() {
+                        commands.entity(settings).despawn_recursive();
+                    }
+                }
+            }
+            PauseButton::QuitToMenu => {
+                state.replace(GameState::Menu).ok();
+            }
+        }
+    }
+}
+
+fn click_rebind_button(
+    mut pending: ResMut<PendingRebind>,
+    player: Res<RebindPlayer>,
+    q_interaction: Query<(&Interaction, &RebindButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in q_interaction.iter() {
+        if *interaction == Interaction::Clicked {
+            pending.0 = Some((player.0, button.0));
+        }
+    }
+}
+
+fn click_rebind_player_button(
+    mut player: ResMut<RebindPlayer>,
+    pending: Res<PendingRebind>,
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<RebindPlayerButton>)>,
+) {
+    for interaction in q_interaction.iter() {
+        if *interaction == Interaction::Clicked && pending.0.is_none() {
+            player.0 = PlayerId((player.0 .0 + 1) % MAX_PLAYERS);
+        }
+    }
+}
+
+fn update_rebind_labels(
+    bindings: Res<PlayerBindings>,
+    pending: Res<PendingRebind>,
+    player: Res<RebindPlayer>,
+    q_buttons: Query<(&RebindButton, &Children)>,
+    q_player_buttons: Query<&Children, With<RebindPlayerButton>>,
+    mut q_text: Query<&mut Text>,
+) {
+    for (button, children) in q_buttons.iter() {
+        if let Ok(mut text) = q_text.get_mut(children[0]) {
+            text.sections[0].value =
+                rebind_label(player.0, button.0, &bindings[player.0], &pending);
+        }
+    }
+    for children in q_player_buttons.iter() {
+        if let Ok(mut text) = q_text.get_mut(children[0]) {
+            text.sections[0].value = format!("Player {}", player.0 .0 + 1);
+        }
+    }
+}
+
+fn rebind_label(
+    player: PlayerId,
+    action: Action,
+    bindings: &InputBindings,
+    pending: &PendingRebind,
+) -> String {
+    let name = REBINDABLE
+        .iter()
+        .find(|(rebindable, _)| *rebindable == action)
+        .map_or("", |(_, name)| name);
+    if pending.0 == Some((player, action)) {
+        return format!("{}: press a key", name);
+    }
+    match bindings.bindings(action).first() {
+        Some(Binding::Key(key)) => format!("{}: {:?}", name, key),
+        Some(Binding::Mouse(button)) => format!("{}: Mouse {:?}", name, button),
+        Some(Binding::Gamepad(button)) => format!("{}: Pad {:?}", name, button),
+        None => format!("{}: unbound", name),
+    }
+}
+
+fn cancel_rebind(mut pending: ResMut<PendingRebind>) {
+    pending.0 = None;
+}
+
+fn spawn_pause_menu(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    texture_assets: Res<TextureAssets>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Paused))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        top: Val::Px(60.0),
+                        left: Val::Px(80.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text {
+                    sections: vec![TextSection {
+                        value: "Paused".to_string(),
+                        style: TextStyle {
+                            font: font_assets.roboto.clone(),
+                            font_size: 70.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    }],
+                    alignment: Default::default(),
+                },
+                ..Default::default()
+            });
+
+            let buttons = [
+                (PauseButton::Resume, "Resume"),
+                (PauseButton::Restart, "Restart"),
+                (PauseButton::Settings, "Settings"),
+                (PauseButton::QuitToMenu, "Quit to Menu"),
+            ];
+            for (i, (button, label)) in buttons.into_iter().enumerate() {
+                spawn_button(
+                    parent,
+                    &font_assets,
+                    &texture_assets,
+                    label.to_string(),
+                    180.0 + i as f32 * 70.0,
+                    80.0,
+                )
+                .insert(button);
+            }
+        });
+}
+
+fn spawn_settings(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    texture_assets: &TextureAssets,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Paused))
+        .insert(SettingsUI)
+        .with_children(|parent| {
+            // labels are filled in by update_rebind_labels
+            spawn_button(
+                parent,
+                font_assets,
+                texture_assets,
+                String::new(),
+                40.0,
+                440.0,
+            )
+            .insert(RebindPlayerButton);
+            for (i, (action, _)) in REBINDABLE.into_iter().enumerate() {
+                spawn_button(
+                    parent,
+                    font_assets,
+                    texture_assets,
+                    String::new(),
+                    105.0 + i as f32 * 65.0,
+                    440.0,
+                )
+                .insert(RebindButton(action));
+            }
+        });
+}
+
+fn spawn_button<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    font_assets: &FontAssets,
+    texture_assets: &TextureAssets,
+    label: String,
+    top: f32,
+    left: f32,
+) -> bevy::ecs::system::EntityCommands<'w, 's, 'a> {
+    let mut button = parent.spawn_bundle(ButtonBundle {
+        image: texture_assets.button_texture.clone().into(),
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                top: Val::Px(top),
+                left: Val::Px(left),
+                ..Default::default()
+            },
+            size: Size::new(Val::Px(260.0), Val::Px(60.0)),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    button.with_children(|parent| {
+        parent.spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(14.0),
+                    left: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: label,
+                    style: TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 30.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        });
+    });
+    button
+}
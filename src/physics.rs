@@ -0,0 +1,83 @@
+//! Rapier, stepped once per gameplay tick rather than once per frame.
+
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{
+    self, JointsEntityMap, ModificationTracker, PhysicsSystems, SimulationToRenderTime,
+    TimestepMode,
+};
+use bevy_rapier2d::prelude::*;
+
+use crate::simulation::{add_tick_systems, SimulationTime, TickStage};
+
+/// Sets up the same resources and systems as [`RapierPhysicsPlugin`], but
+/// runs the systems inside the gameplay tick so bodies spawned by one tick
+/// take part in the next, however many ticks a frame runs.
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PhysicsPipeline::new())
+            .insert_resource(QueryPipeline::new())
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::FixedTimestep,
+                ..Default::default()
+            })
+            .insert_resource(IntegrationParameters::default())
+            .insert_resource(BroadPhase::new())
+            .insert_resource(NarrowPhase::new())
+            .insert_resource(IslandManager::new())
+            .insert_resource(ImpulseJointSet::new())
+            .insert_resource(MultibodyJointSet::new())
+            .insert_resource(CCDSolver::new())
+            .insert_resource(SimulationToRenderTime::default())
+            .insert_resource(JointsEntityMap::default())
+            .insert_resource(ModificationTracker::default())
+            .insert_resource(PhysicsHooksWithQueryObject::<NoUserData>(Box::new(())))
+            .add_event::<IntersectionEvent>()
+            .add_event::<ContactEvent>()
+            .add_system_to_stage(CoreStage::PostUpdate, physics::collect_removals);
+
+        add_tick_systems(
+            app,
+            TickStage::Prepare,
+            SystemSet::new()
+                .with_system(
+                    physics::attach_bodies_and_colliders_system
+                        .label(PhysicsSystems::AttachBodiesAndColliders),
+                )
+                .with_system(physics::create_joints_system.label(PhysicsSystems::CreateJoints))
+                .with_system(match_tick_step),
+        );
+        add_tick_systems(
+            app,
+            TickStage::Attach,
+            SystemSet::new().with_system(
+                physics::finalize_collider_attach_to_bodies
+                    .label(PhysicsSystems::FinalizeColliderAttachToBodies),
+            ),
+        );
+        add_tick_systems(
+            app,
+            TickStage::Gameplay,
+            SystemSet::new().with_system(
+                physics::step_world_system::<NoUserData>.label(PhysicsSystems::StepWorld),
+            ),
+        );
+        add_tick_systems(
+            app,
+            TickStage::Sync,
+            SystemSet::new()
+                .with_system(physics::sync_transforms.label(PhysicsSystems::SyncTransforms)),
+        );
+    }
+}
+
+/// Steps physics by exactly one tick.
+fn match_tick_step(
+    time: Res<SimulationTime>,
+    mut integration_parameters: ResMut<IntegrationParameters>,
+) {
+    if integration_parameters.dt != time.delta_seconds() {
+        integration_parameters.dt = time.delta_seconds();
+    }
+}
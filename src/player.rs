@@ -1,51 +1,101 @@
+use crate::cleanup::StateScoped;
 use crate::clock::Clock;
-use crate::components::{Dash, ObjectBundle, Player, Speed};
+use crate::components::{
+    Dash, MainCamera, ObjectBundle, Player, PlayerId, Speed, GROUP_ALARM, GROUP_GRANDFATHER,
+    GROUP_PLAYER, MAX_PLAYERS,
+};
+use crate::config::GameConfig;
+use crate::difficulty::Difficulty;
+use crate::events::{ClockExpired, PlayerDashed};
+use crate::input::PlayerInputs;
 use crate::loading::FontAssets;
 use crate::loading::TextureAssets;
 use crate::reflector::{spawn_reflector, ReflectorPlugin, ReflectorToggle};
+use crate::simulation::{add_gameplay_systems, GameplaySystem, Interpolated, SimulationTime};
 use crate::GameState;
 
 use bevy::prelude::*;
 use bevy_rapier2d::na::Vector2;
+use bevy_rapier2d::physics::PhysicsSystems;
 use bevy_rapier2d::prelude::*;
 
+/// Pixels between players standing side by side at the start of a run.
+const PLAYER_SPACING: f32 = 120.0;
+
+/// How many players share a run, picked on the main menu.
+pub struct PlayerCount(pub usize);
+
+impl Default for PlayerCount {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
 #[derive(Component)]
-pub struct PlayerClockUI;
+pub struct PlayerClockUI(PlayerId);
+
+use bundle::PlayerBundle;
+
+// allowed for the same reason as `AlarmBundle`
+#[allow(clippy::forget_non_drop)]
+mod bundle {
+    use super::*;
 
-#[derive(Bundle)]
-struct PlayerBundle {
-    clock: Clock,
-    dash: Dash,
-    speed: Speed,
-    _player: Player,
+    #[derive(Bundle)]
+    pub(super) struct PlayerBundle {
+        pub(super) clock: Clock,
+        pub(super) dash: Dash,
+        pub(super) speed: Speed,
+        pub(super) id: PlayerId,
+        pub(super) _player: Player,
 
-    #[bundle]
-    object: ObjectBundle,
+        #[bundle]
+        pub(super) object: ObjectBundle,
+    }
 }
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(ReflectorPlugin)
+        app.init_resource::<PlayerCount>()
+            .add_plugin(ReflectorPlugin)
+            .add_startup_system(spawn_camera)
             .add_system_set(
                 SystemSet::on_enter(GameState::Playing)
-                    .with_system(spawn_player)
-                    .with_system(spawn_camera)
-                    .with_system(spawn_clock_ui),
+                    .with_system(spawn_player.label(GameplaySystem::SpawnPlayer))
+                    .with_system(spawn_clock_ui.after(GameplaySystem::SpawnGrandfathers)),
             )
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing)
-                    .with_system(move_player)
-                    .with_system(toggle_reflector)
-                    .with_system(handle_game_over)
-                    .with_system(update_clock_ui),
-            );
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(update_clock_ui));
+        add_gameplay_systems(
+            app,
+            SystemSet::new()
+                .with_system(
+                    move_player
+                        .label(GameplaySystem::Control)
+                        .after(GameplaySystem::Timers)
+                        .before(PhysicsSystems::StepWorld),
+                )
+                .with_system(
+                    toggle_reflector
+                        .label(GameplaySystem::Control)
+                        .after(GameplaySystem::Timers)
+                        .before(PhysicsSystems::StepWorld),
+                )
+                .with_system(knock_out_players.after(GameplaySystem::Outcome))
+                .with_system(
+                    handle_game_over
+                        .label(GameplaySystem::Defeat)
+                        .after(GameplaySystem::Outcome),
+                ),
+        );
     }
 }
 
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
 }
 
 // fn spawn_background(mut commands: Commands, textures: Res<TextureAssets>) {
@@ -59,109 +109,180 @@ fn spawn_camera(mut commands: Commands) {
 //     });
 // }
 
-fn spawn_player(mut commands: Commands, textures: Res<TextureAssets>) {
-    let player_time = 60.0 * 5.0;
-    commands
-        .spawn_bundle(PlayerBundle {
-            clock: Clock::new(player_time),
-            dash: Dash {
-                speed: 1200.0,
-                is_dashing: false,
-                duration: 0.1,
-            },
-            speed: Speed(300.0),
-            object: ObjectBundle {
-                rigid_body: RigidBodyBundle {
-                    body_type: RigidBodyType::Dynamic.into(),
-                    mass_properties: RigidBodyMassPropsFlags::ROTATION_LOCKED.into(),
-                    position: Vec2::new(0.0, 0.0).into(),
-                    velocity: RigidBodyVelocity {
-                        linvel: Vec2::new(0.0, 0.0).into(),
-                        angvel: 0.0,
-                    }
-                    .into(),
-                    ..Default::default()
-                },
-                collider: ColliderBundle {
-                    shape: ColliderShape::cuboid(5.0, 15.0).into(),
-                    collider_type: ColliderType::Solid.into(),
-                    ..Default::default()
+/// Sets each player apart from the others. The first player keeps the
+/// sprite's own colours.
+pub fn player_tint(player: PlayerId) -> Color {
+    match player.0 {
+        0 => Color::WHITE,
+        _ => Color::rgb(0.6, 0.8, 1.0),
+    }
+}
+
+/// The living player nearest to `position`, given each player's entity,
+/// position and clock. `None` once every player's clock has run out.
+pub fn nearest_living_player<'a>(
+    players: impl IntoIterator<Item = (Entity, Vec2, &'a Clock)>,
+    position: Vec2,
+) -> Option<(Entity, Vec2)> {
+    players
+        .into_iter()
+        .filter(|(_, _, clock)| clock.time > 0.0)
+        .map(|(player, at, _)| (player, at))
+        .min_by(|(_, a), (_, b)| {
+            a.distance_squared(position)
+                .total_cmp(&b.distance_squared(position))
+        })
+}
+
+/// Spawns every player side by side in the middle of the stage.
+fn spawn_player(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    player_count: Res<PlayerCount>,
+) {
+    let scale = config.difficulty.scale(*difficulty);
+    let count = player_count.0.clamp(1, MAX_PLAYERS);
+    for i in 0..count {
+        let id = PlayerId(i);
+        let position = Vec2::new((i as f32 - (count - 1) as f32 / 2.0) * PLAYER_SPACING, 0.0);
+        commands
+            .spawn_bundle(PlayerBundle {
+                clock: Clock::new(config.player.clock * scale.player_clock),
+                dash: Dash {
+                    speed: config.player.dash_speed,
+                    is_dashing: false,
+                    duration: config.player.dash_duration,
                 },
-                sprite: SpriteBundle {
-                    texture: textures.player_texture_64.clone(),
-                    transform: Transform {
-                        translation: Vec3::new(0.0, 0.0, 1.0),
+                speed: Speed(config.player.speed),
+                id,
+                object: ObjectBundle {
+                    rigid_body: RigidBodyBundle {
+                        body_type: RigidBodyType::Dynamic.into(),
+                        mass_properties: RigidBodyMassPropsFlags::ROTATION_LOCKED.into(),
+                        position: position.into(),
+                        velocity: RigidBodyVelocity {
+                            linvel: Vec2::new(0.0, 0.0).into(),
+                            angvel: 0.0,
+                        }
+                        .into(),
+                        ..Default::default()
+                    },
+                    collider: ColliderBundle {
+                        shape: ColliderShape::cuboid(5.0, 15.0).into(),
+                        collider_type: ColliderType::Solid.into(),
+                        flags: ColliderFlags {
+                            collision_groups: InteractionGroups::new(
+                                GROUP_PLAYER,
+                                GROUP_ALARM | GROUP_GRANDFATHER,
+                            ),
+                            ..Default::default()
+                        }
+                        .into(),
+                        ..Default::default()
+                    },
+                    sprite: SpriteBundle {
+                        sprite: Sprite {
+                            color: player_tint(id),
+                            ..Default::default()
+                        },
+                        texture: textures.player_texture_64.clone(),
+                        transform: Transform {
+                            translation: position.extend(1.0),
+                            ..Default::default()
+                        },
                         ..Default::default()
                     },
-                    ..Default::default()
                 },
-            },
-            _player: Player,
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(spawn_reflector(textures));
-        })
-        .insert(ColliderPositionSync::Discrete);
+                _player: Player,
+            })
+            .insert(StateScoped(GameState::Playing))
+            .insert(Interpolated::default())
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(spawn_reflector(&textures, &config))
+                    .insert(id)
+                    .insert(Interpolated::default());
+            })
+            .insert(ColliderPositionSync::Discrete);
+    }
 }
 
+/// Players whose clock has run out stop holding their reflector up.
 fn toggle_reflector(
-    keyboard_input: Res<Input<KeyCode>>,
+    inputs: Res<PlayerInputs>,
     mut ev_reflector_toggle: EventWriter<ReflectorToggle>,
+    q_player: Query<(&PlayerId, &Clock), With<Player>>,
 ) {
-    if keyboard_input.pressed(KeyCode::LShift) {
-        ev_reflector_toggle.send(ReflectorToggle(true));
-    } else {
-        ev_reflector_toggle.send(ReflectorToggle(false));
+    for (player, clock) in q_player.iter() {
+        ev_reflector_toggle.send(ReflectorToggle {
+            player: *player,
+            held: inputs[*player].reflect && clock.time > 0.0,
+        });
     }
 }
 
+type MovingPlayer<'a> = (
+    Entity,
+    &'a PlayerId,
+    &'a Clock,
+    &'a Speed,
+    &'a mut Dash,
+    &'a RigidBodyPositionComponent,
+    &'a mut RigidBodyVelocityComponent,
+);
+
 fn move_player(
     window: Res<WindowDescriptor>,
-    keyboard_input: Res<Input<KeyCode>>,
+    mut inputs: ResMut<PlayerInputs>,
     rapier_parameters: Res<RapierConfiguration>,
-    time: Res<Time>,
-    mut q_player: Query<
-        (
-            &Speed,
-            &mut Dash,
-            &RigidBodyPositionComponent,
-            &mut RigidBodyVelocityComponent,
-        ),
-        With<Player>,
-    >,
+    config: Res<GameConfig>,
+    time: Res<SimulationTime>,
+    mut ev_dashed: EventWriter<PlayerDashed>,
+    mut q_player: Query<MovingPlayer, With<Player>>,
 ) {
-    for (speed, mut dash, rb_pos, mut rb_vels) in q_player.iter_mut() {
-        let up = keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
-        let down = keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
-        let left = keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
-        let right = keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right);
-        let dashing = keyboard_input.just_pressed(KeyCode::Space) && (up || down || left || right);
-
-        let x_axis = -(left as i8) + right as i8;
-        let y_axis = -(down as i8) + up as i8;
-
-        let mut move_delta = Vector2::new(x_axis as f32, y_axis as f32);
-        if move_delta != Vector2::zeros() {
-            move_delta /= move_delta.magnitude() * rapier_parameters.scale;
+    for (player, id, clock, speed, mut dash, rb_pos, mut rb_vels) in q_player.iter_mut() {
+        // a player whose clock has run out sits the rest of the run out
+        if clock.time <= 0.0 {
+            rb_vels.linvel = Vector2::zeros();
+            continue;
         }
+        let input = &inputs[*id];
+        let up = input.movement.y > 0.0;
+        let down = input.movement.y < 0.0;
+        let left = input.movement.x < 0.0;
+        let right = input.movement.x > 0.0;
+        let dashing = input.dash && (up || down || left || right);
+
+        // analog sticks move slower when only partly tilted, but never faster
+        // than full speed, even diagonally
+        let mut move_delta = Vector2::new(input.movement.x, input.movement.y);
+        if move_delta.magnitude() > 1.0 {
+            move_delta /= move_delta.magnitude();
+        }
+        move_delta /= rapier_parameters.scale;
+        let dash_delta =
+            move_delta.try_normalize(0.0).unwrap_or_else(Vector2::zeros) / rapier_parameters.scale;
 
         if dash.is_dashing {
-            rb_vels.linvel = move_delta * dash.speed;
+            rb_vels.linvel = dash_delta * dash.speed;
             dash.duration -= time.delta_seconds();
             if dash.duration <= 0.0 {
                 dash.is_dashing = false;
-                dash.duration = 0.1;
+                dash.duration = config.player.dash_duration;
             }
         } else if dashing {
-            rb_vels.linvel = move_delta * dash.speed;
+            rb_vels.linvel = dash_delta * dash.speed;
             dash.is_dashing = true;
+            ev_dashed.send(PlayerDashed { player });
         } else {
             rb_vels.linvel = move_delta * speed.0;
         }
 
         let position = rb_pos.position.translation.vector.data.0[0];
-        let bounds_x = window.width as f32 / 2.0;
-        let bounds_y = window.height as f32 / 2.0;
+        let bounds_x = window.width / 2.0;
+        let bounds_y = window.height / 2.0;
         let x = position[0];
         let y = position[1];
 
@@ -178,56 +299,95 @@ fn move_player(
             rb_vels.linvel.data.0[0][1] = 0.0;
         }
     }
+    for i in 0..MAX_PLAYERS {
+        inputs[PlayerId(i)].dash = false;
+    }
 }
 
+/// Takes a player whose clock has run out out of play, while anyone else is
+/// still going: alarms pass straight through them from then on.
+fn knock_out_players(
+    mut ev_clock_expired: EventReader<ClockExpired>,
+    mut q_player: Query<(&mut ColliderFlagsComponent, &mut Sprite), With<Player>>,
+) {
+    for expired in ev_clock_expired.iter() {
+        if let Ok((mut flags, mut sprite)) = q_player.get_mut(expired.entity) {
+            flags.collision_groups = InteractionGroups::none();
+            sprite.color.set_a(0.35);
+        }
+    }
+}
+
+/// The run is lost once every player's clock has run out.
 fn handle_game_over(
-    mut commands: Commands,
     q_player: Query<&Clock, With<Player>>,
-    q_entities: Query<Entity>,
     mut state: ResMut<State<GameState>>,
+    mut time: ResMut<SimulationTime>,
 ) {
-    let player_clock = q_player.single();
-    if player_clock.time <= 0.0 {
-        for entity in q_entities.iter() {
-            commands.entity(entity).despawn_recursive();
-        }
-        state.set(GameState::GameOver).unwrap();
+    if !q_player.is_empty() && q_player.iter().all(|clock| clock.time <= 0.0) {
+        state.set(GameState::GameOver).ok();
+        time.stop();
     }
 }
 
-fn spawn_clock_ui(mut commands: Commands, font_assets: Res<FontAssets>) {
-    commands
-        .spawn_bundle(TextBundle {
-            style: Style {
-                position_type: PositionType::Absolute,
-                position: Rect {
-                    top: Val::Px(5.0),
-                    left: Val::Percent(45.0),
+/// One clock per player across the top of the screen, labelled when there
+/// is more than one.
+fn spawn_clock_ui(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    player_count: Res<PlayerCount>,
+) {
+    let count = player_count.0.clamp(1, MAX_PLAYERS);
+    for i in 0..count {
+        let id = PlayerId(i);
+        let (left, label) = if count == 1 {
+            (45.0, String::new())
+        } else {
+            (25.0 + 40.0 * i as f32, format!("P{} ", i + 1))
+        };
+        let style = bevy::text::TextStyle {
+            font: font_assets.roboto.clone(),
+            font_size: 30.0,
+            color: player_tint(id),
+        };
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        top: Val::Px(5.0),
+                        left: Val::Percent(left),
+                        ..Default::default()
+                    },
                     ..Default::default()
                 },
+                text: Text {
+                    sections: vec![
+                        TextSection {
+                            value: label,
+                            style: style.clone(),
+                        },
+                        TextSection {
+                            value: "00:00".to_string(),
+                            style,
+                        },
+                    ],
+                    alignment: Default::default(),
+                },
                 ..Default::default()
-            },
-            text: Text {
-                sections: vec![TextSection {
-                    value: "00:00".to_string(),
-                    style: bevy::text::TextStyle {
-                        font: font_assets.roboto.clone(),
-                        font_size: 30.0,
-                        color: Color::rgb(1., 1., 1.),
-                    },
-                }],
-                alignment: Default::default(),
-            },
-            ..Default::default()
-        })
-        .insert(PlayerClockUI);
+            })
+            .insert(StateScoped(GameState::Playing))
+            .insert(PlayerClockUI(id));
+    }
 }
 
 fn update_clock_ui(
-    mut q_text: Query<&mut Text, With<PlayerClockUI>>,
-    q_clock: Query<&Clock, With<Player>>,
+    mut q_text: Query<(&mut Text, &PlayerClockUI)>,
+    q_clock: Query<(&Clock, &PlayerId), With<Player>>,
 ) {
-    let clock = q_clock.single();
-    let mut text = q_text.single_mut();
-    text.sections[0].value = clock.to_string();
+    for (mut text, ui) in q_text.iter_mut() {
+        if let Some((clock, _)) = q_clock.iter().find(|(_, id)| **id == ui.0) {
+            text.sections[1].value = clock.to_string();
+        }
+    }
 }
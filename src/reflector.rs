@@ -1,74 +1,319 @@
-use crate::loading::TextureAssets;
+use crate::cleanup::StateScoped;
+use crate::components::{PlayerId, GROUP_ALARM, GROUP_REFLECTOR, MAX_PLAYERS};
+use crate::config::GameConfig;
+use crate::input::PlayerInputs;
+use crate::loading::{FontAssets, TextureAssets};
+use crate::player::{player_tint, PlayerCount};
+use crate::simulation::{add_gameplay_systems, GameplaySystem, SimulationTime};
 use crate::GameState;
 use bevy::prelude::*;
+use bevy_rapier2d::physics::PhysicsSystems;
 use bevy_rapier2d::prelude::*;
+use std::f32::consts::FRAC_PI_2;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ReflectorState {
     Active,
     InActive,
+    /// Stamina ran out; the reflector can't be raised until the cooldown ends.
+    Cooldown,
 }
 
 #[derive(Component)]
 pub struct Reflector {
     pub state: ReflectorState,
+    /// Seconds since the reflector was last raised.
+    pub active_time: f32,
+    /// Direction the reflector faces, in radians from the positive x axis.
+    pub angle: f32,
+    /// Seconds the reflector can still be held up.
+    pub stamina: f32,
+    pub cooldown: Timer,
 }
 
-#[derive(Bundle)]
-pub struct ReflectorBundle {
-    _reflector: Reflector,
+#[derive(Component)]
+struct StaminaBarUI(PlayerId);
+
+pub use bundle::ReflectorBundle;
+
+// allowed for the same reason as `AlarmBundle`
+#[allow(clippy::forget_non_drop)]
+mod bundle {
+    use super::*;
 
-    #[bundle]
-    collider: ColliderBundle,
-    #[bundle]
-    sprite: SpriteBundle,
+    #[derive(Bundle)]
+    pub struct ReflectorBundle {
+        pub(super) _reflector: Reflector,
+
+        #[bundle]
+        pub(super) collider: ColliderBundle,
+        #[bundle]
+        pub(super) sprite: SpriteBundle,
+    }
 }
 
-pub struct ReflectorToggle(pub bool);
+/// Whether a player is holding their reflector up this tick.
+pub struct ReflectorToggle {
+    pub player: PlayerId,
+    pub held: bool,
+}
 
 pub struct ReflectorPlugin;
 
 impl Plugin for ReflectorPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<ReflectorToggle>().add_system_set(
-            SystemSet::on_update(GameState::Playing).with_system(update_reflector_state),
+        app.add_event::<ReflectorToggle>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(spawn_stamina_ui.after(GameplaySystem::SpawnGrandfathers)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(update_stamina_ui),
+            );
+        add_gameplay_systems(
+            app,
+            SystemSet::new()
+                .with_system(
+                    update_reflector_state
+                        .label(GameplaySystem::Reflector)
+                        .after(GameplaySystem::Control)
+                        .before(PhysicsSystems::StepWorld),
+                )
+                .with_system(
+                    aim_reflector
+                        .label(GameplaySystem::Reflector)
+                        .after(GameplaySystem::Control)
+                        .before(PhysicsSystems::StepWorld),
+                ),
         );
     }
 }
 
-pub fn spawn_reflector(textures: Res<TextureAssets>) -> ReflectorBundle {
+pub fn spawn_reflector(textures: &TextureAssets, config: &GameConfig) -> ReflectorBundle {
+    let orbit_radius = config.reflector.orbit_radius;
     ReflectorBundle {
         collider: ColliderBundle {
-            position: Vec2::new(0.0, 40.0).into(),
+            position: Vec2::new(0.0, orbit_radius).into(),
             shape: ColliderShape::cuboid(2.0, 1.0).into(),
             collider_type: ColliderType::Solid.into(),
+            flags: ColliderFlags {
+                collision_groups: InteractionGroups::none(),
+                solver_groups: InteractionGroups::none(),
+                ..Default::default()
+            }
+            .into(),
             ..Default::default()
         },
         sprite: SpriteBundle {
+            sprite: Sprite {
+                color: ReflectorState::InActive.color(),
+                ..Default::default()
+            },
             texture: textures.reflector_texture.clone(),
             transform: Transform {
-                translation: Vec3::new(0.0, 40.0, 0.0),
+                translation: Vec3::new(0.0, orbit_radius, 0.0),
                 ..Default::default()
             },
             ..Default::default()
         },
         _reflector: Reflector {
             state: ReflectorState::InActive,
+            active_time: 0.0,
+            angle: FRAC_PI_2,
+            stamina: config.reflector.stamina,
+            cooldown: Timer::from_seconds(config.reflector.cooldown, false),
         },
     }
 }
 
+impl ReflectorState {
+    fn color(self) -> Color {
+        match self {
+            ReflectorState::Active => Color::WHITE,
+            ReflectorState::InActive => Color::rgba(1.0, 1.0, 1.0, 0.35),
+            ReflectorState::Cooldown => Color::rgba(1.0, 0.3, 0.3, 0.35),
+        }
+    }
+}
+
+/// Raises or lowers the reflector. Holding it up drains stamina, and running
+/// dry locks it down for the cooldown. Only a raised reflector collides.
 pub fn update_reflector_state(
     mut ev_reflector_toggle: EventReader<ReflectorToggle>,
-    mut q_reflector: Query<&mut Reflector>,
+    mut q_reflector: Query<(
+        &PlayerId,
+        &mut Reflector,
+        &mut Sprite,
+        &mut ColliderFlagsComponent,
+    )>,
+    config: Res<GameConfig>,
+    time: Res<SimulationTime>,
 ) {
+    let mut held = [None; MAX_PLAYERS];
     for toggle in ev_reflector_toggle.iter() {
-        let mut reflector = q_reflector.single_mut();
-        if toggle.0 {
-            // println!("reflector active");
-            reflector.state = ReflectorState::Active;
+        held[toggle.player.0] = Some(toggle.held);
+    }
+    let dt = time.delta_seconds();
+
+    for (player, mut reflector, mut sprite, mut flags) in q_reflector.iter_mut() {
+        let held = held[player.0].unwrap_or(reflector.state == ReflectorState::Active);
+        let state = match reflector.state {
+            ReflectorState::Cooldown => {
+                if reflector.cooldown.tick(time.delta()).finished() {
+                    ReflectorState::InActive
+                } else {
+                    ReflectorState::Cooldown
+                }
+            }
+            _ if held => {
+                if reflector.state == ReflectorState::Active {
+                    reflector.active_time += dt;
+                } else {
+                    reflector.active_time = 0.0;
+                }
+                reflector.stamina -= dt;
+                if reflector.stamina <= 0.0 {
+                    reflector.stamina = 0.0;
+                    reflector.cooldown.reset();
+                    ReflectorState::Cooldown
+                } else {
+                    ReflectorState::Active
+                }
+            }
+            _ => ReflectorState::InActive,
+        };
+        if state != ReflectorState::Active {
+            reflector.stamina = (reflector.stamina + config.reflector.stamina_regen * dt)
+                .min(config.reflector.stamina);
+        }
+
+        if state != reflector.state {
+            let groups = if state == ReflectorState::Active {
+                InteractionGroups::new(GROUP_REFLECTOR, GROUP_ALARM)
+            } else {
+                InteractionGroups::none()
+            };
+            flags.collision_groups = groups;
+            flags.solver_groups = groups;
+            sprite.color = state.color();
+            reflector.state = state;
+        }
+    }
+}
+
+/// One stamina bar per player, along the bottom of the screen.
+fn spawn_stamina_ui(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    player_count: Res<PlayerCount>,
+) {
+    let count = player_count.0.clamp(1, MAX_PLAYERS);
+    for i in 0..count {
+        let id = PlayerId(i);
+        let left = 10.0 + 180.0 * i as f32;
+        let label = if count == 1 {
+            "Reflector".to_string()
         } else {
-            // println!("reflector inactive");
-            reflector.state = ReflectorState::InActive;
+            format!("P{} Reflector", i + 1)
+        };
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Px(10.0),
+                        left: Val::Px(left),
+                        ..Default::default()
+                    },
+                    size: Size::new(Val::Px(150.0), Val::Px(12.0)),
+                    ..Default::default()
+                },
+                color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                ..Default::default()
+            })
+            .insert(StateScoped(GameState::Playing))
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                            ..Default::default()
+                        },
+                        color: Color::rgb(0.9, 0.9, 0.9).into(),
+                        ..Default::default()
+                    })
+                    .insert(StaminaBarUI(id));
+            });
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Px(25.0),
+                        left: Val::Px(left),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text {
+                    sections: vec![TextSection {
+                        value: label,
+                        style: bevy::text::TextStyle {
+                            font: font_assets.roboto.clone(),
+                            font_size: 18.0,
+                            color: player_tint(id),
+                        },
+                    }],
+                    alignment: Default::default(),
+                },
+                ..Default::default()
+            })
+            .insert(StateScoped(GameState::Playing));
+    }
+}
+
+fn update_stamina_ui(
+    config: Res<GameConfig>,
+    q_reflector: Query<(&Reflector, &PlayerId)>,
+    mut q_bar: Query<(&mut Style, &mut UiColor, &StaminaBarUI)>,
+) {
+    for (mut style, mut color, bar) in q_bar.iter_mut() {
+        let reflector = match q_reflector.iter().find(|(_, id)| **id == bar.0) {
+            Some((reflector, _)) => reflector,
+            None => continue,
+        };
+        style.size.width = Val::Percent(100.0 * reflector.stamina / config.reflector.stamina);
+        color.0 = if reflector.state == ReflectorState::Cooldown {
+            Color::rgb(0.9, 0.3, 0.3)
+        } else {
+            Color::rgb(0.9, 0.9, 0.9)
+        };
+    }
+}
+
+/// Turns each reflector around its player to face the direction they aim in.
+pub fn aim_reflector(
+    inputs: Res<PlayerInputs>,
+    config: Res<GameConfig>,
+    mut q_reflector: Query<(
+        &PlayerId,
+        &mut Reflector,
+        &mut Transform,
+        &mut ColliderParentComponent,
+    )>,
+) {
+    for (player, mut reflector, mut transform, mut co_parent) in q_reflector.iter_mut() {
+        let aim = inputs[*player].aim;
+        if let Some(direction) = aim.and_then(|direction| direction.try_normalize()) {
+            reflector.angle = direction.y.atan2(direction.x);
         }
+        let offset =
+            Vec2::new(reflector.angle.cos(), reflector.angle.sin()) * config.reflector.orbit_radius;
+        // the reflector texture faces up at rest
+        let rotation = reflector.angle - FRAC_PI_2;
+
+        transform.translation = offset.extend(transform.translation.z);
+        transform.rotation = Quat::from_rotation_z(rotation);
+        co_parent.pos_wrt_parent = Isometry::new(offset.into(), rotation);
     }
 }
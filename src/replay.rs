@@ -0,0 +1,273 @@
+//! Records the input of every run so it can be played back exactly, for
+//! example to reproduce a balance bug from a playtester's run.
+
+use bevy::prelude::*;
+use bevy_rapier2d::physics::PhysicsSystems;
+use serde::{Deserialize, Serialize};
+
+use crate::components::PlayerId;
+use crate::config::GameConfig;
+use crate::difficulty::Difficulty;
+use crate::endless::GameMode;
+use crate::input::PlayerInputs;
+use crate::pattern::PatternLibrary;
+use crate::player::PlayerCount;
+use crate::rng::{GameRng, SeedOverride};
+use crate::simulation::{add_gameplay_systems, GameplaySystem};
+use crate::stats::RunStats;
+use crate::storage;
+use crate::wave::WaveTimeline;
+use crate::GameState;
+
+const REPLAY_FILE: &str = "last-run.replay.ron";
+
+/// Everything needed to play a run again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub config: GameConfig,
+    pub waves: WaveTimeline,
+    pub patterns: PatternLibrary,
+    pub difficulty: Difficulty,
+    pub mode: GameMode,
+    pub seed: u64,
+    /// How many players shared the run.
+    pub players: usize,
+    /// One entry per gameplay tick, holding each player's input in
+    /// [`PlayerId`] order.
+    pub frames: Vec<Vec<ReplayFrame>>,
+}
+
+/// One player's input for one gameplay tick.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub movement: Vec2,
+    pub dash: bool,
+    pub reflect: bool,
+    pub aim: Option<Vec2>,
+}
+
+impl Replay {
+    pub fn from_ron(contents: &str) -> Result<Self, String> {
+        ron::de::from_str(contents).map_err(|err| err.to_string())
+    }
+
+    pub fn save(&self) {
+        let result = ron::ser::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::save(REPLAY_FILE, &contents));
+        if let Err(err) = result {
+            warn!("could not save {}: {}", REPLAY_FILE, err);
+        }
+    }
+}
+
+/// The replay of the most recently finished run.
+#[derive(Default)]
+pub struct LastReplay(pub Option<Replay>);
+
+/// Input recorded so far in the current run.
+#[derive(Default)]
+struct Recording(Vec<Vec<ReplayFrame>>);
+
+/// A replay driving the current run instead of the player. Inserting it
+/// starts a run from the menu with the replay's config; it is removed again
+/// once the run is over, and the settings it replaced are put back.
+pub struct ReplayPlayback {
+    replay: Replay,
+    frame: usize,
+    replaced: Option<Settings>,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            frame: 0,
+            replaced: None,
+        }
+    }
+}
+
+/// The settings a replay takes over while it plays.
+struct Settings {
+    config: GameConfig,
+    waves: WaveTimeline,
+    patterns: PatternLibrary,
+    difficulty: Difficulty,
+    mode: GameMode,
+    players: usize,
+    seed: Option<u64>,
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastReplay>()
+            .init_resource::<Recording>()
+            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(start_playback))
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_recording))
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(stop_playback))
+            .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(finish_recording))
+            .add_system_set(SystemSet::on_enter(GameState::GameWin).with_system(finish_recording));
+        add_gameplay_systems(
+            app,
+            SystemSet::new().with_system(
+                replay_input
+                    .before(GameplaySystem::Timers)
+                    .before(GameplaySystem::Movement)
+                    .before(PhysicsSystems::StepWorld),
+            ),
+        );
+    }
+}
+
+/// Writes out the replay of each finished run. Left out when running headless,
+/// so tests never touch the real save file.
+pub fn save_replay(last_replay: Res<LastReplay>) {
+    if last_replay.is_changed() && !last_replay.is_added() {
+        if let Some(replay) = &last_replay.0 {
+            replay.save();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_playback(
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut config: ResMut<GameConfig>,
+    mut waves: ResMut<WaveTimeline>,
+    mut patterns: ResMut<PatternLibrary>,
+    mut difficulty: ResMut<Difficulty>,
+    mut mode: ResMut<GameMode>,
+    mut player_count: ResMut<PlayerCount>,
+    mut seed_override: ResMut<SeedOverride>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if let Some(mut playback) = playback {
+        if playback.replaced.is_none() {
+            playback.replaced = Some(Settings {
+                config: config.clone(),
+                waves: waves.clone(),
+                patterns: patterns.clone(),
+                difficulty: *difficulty,
+                mode: *mode,
+                players: player_count.0,
+                seed: seed_override.0,
+            });
+        }
+        *config = playback.replay.config.clone();
+        *waves = playback.replay.waves.clone();
+        *patterns = playback.replay.patterns.clone();
+        *difficulty = playback.replay.difficulty;
+        *mode = playback.replay.mode;
+        player_count.0 = playback.replay.players;
+        seed_override.0 = Some(playback.replay.seed);
+        state.set(GameState::Playing).ok();
+    }
+}
+
+fn start_recording(mut recording: ResMut<Recording>) {
+    recording.0.clear();
+}
+
+/// Feeds the next recorded frames to the gameplay systems in place of the
+/// players' input, then records the input this tick runs with.
+fn replay_input(
+    playback: Option<ResMut<ReplayPlayback>>,
+    player_count: Res<PlayerCount>,
+    mut recording: ResMut<Recording>,
+    mut inputs: ResMut<PlayerInputs>,
+) {
+    if let Some(mut playback) = playback {
+        let recorded = playback.replay.frames.len();
+        match playback.replay.frames.get(playback.frame).cloned() {
+            Some(frames) => {
+                playback.frame += 1;
+                for (i, frame) in frames.into_iter().enumerate() {
+                    let input = &mut inputs[PlayerId(i)];
+                    input.movement = frame.movement;
+                    input.dash = frame.dash;
+                    input.reflect = frame.reflect;
+                    input.aim = frame.aim;
+                }
+            }
+            // the players have had control back since the replay ran out
+            None if playback.frame > recorded => {}
+            None => {
+                warn!("the replay ended before the run did, handing control back");
+                playback.frame += 1;
+            }
+        }
+    }
+
+    let frames = inputs
+        .iter()
+        .take(player_count.0)
+        .map(|input| ReplayFrame {
+            movement: input.movement,
+            dash: input.dash,
+            reflect: input.reflect,
+            aim: input.aim,
+        })
+        .collect();
+    recording.0.push(frames);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn finish_recording(
+    stats: Res<RunStats>,
+    mut recording: ResMut<Recording>,
+    mut last_replay: ResMut<LastReplay>,
+    config: Res<GameConfig>,
+    waves: Res<WaveTimeline>,
+    patterns: Res<PatternLibrary>,
+    difficulty: Res<Difficulty>,
+    mode: Res<GameMode>,
+    player_count: Res<PlayerCount>,
+    rng: Res<GameRng>,
+) {
+    // watching a replay leaves the one being watched in place
+    if stats.replay {
+        recording.0.clear();
+        return;
+    }
+    last_replay.0 = Some(Replay {
+        config: config.clone(),
+        waves: waves.clone(),
+        patterns: patterns.clone(),
+        difficulty: *difficulty,
+        mode: *mode,
+        seed: rng.seed(),
+        players: player_count.0,
+        frames: std::mem::take(&mut recording.0),
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn stop_playback(
+    mut commands: Commands,
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut config: ResMut<GameConfig>,
+    mut waves: ResMut<WaveTimeline>,
+    mut patterns: ResMut<PatternLibrary>,
+    mut difficulty: ResMut<Difficulty>,
+    mut mode: ResMut<GameMode>,
+    mut player_count: ResMut<PlayerCount>,
+    mut seed_override: ResMut<SeedOverride>,
+) {
+    let mut playback = match playback {
+        Some(playback) => playback,
+        None => return,
+    };
+    commands.remove_resource::<ReplayPlayback>();
+    if let Some(replaced) = playback.replaced.take() {
+        *config = replaced.config;
+        *waves = replaced.waves;
+        *patterns = replaced.patterns;
+        *difficulty = replaced.difficulty;
+        *mode = replaced.mode;
+        player_count.0 = replaced.players;
+        seed_override.0 = replaced.seed;
+    }
+}
@@ -0,0 +1,85 @@
+//! The one source of randomness in a run. Everything random draws from
+//! [`GameRng`], which is reseeded as each run starts, so a seed always plays
+//! out the same run.
+
+use bevy::prelude::*;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::config::GameConfig;
+use crate::simulation::GameplaySystem;
+use crate::storage;
+use crate::GameState;
+
+/// A seed that takes precedence over the config, such as one given on the
+/// command line or the seed of a replay being played.
+#[derive(Default)]
+pub struct SeedOverride(pub Option<u64>);
+
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// The seed the current run started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(daily_seed())
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Today's date in UTC as a `yyyymmdd` number, so everyone playing on the
+/// same day gets the same run unless they pick a seed.
+pub fn daily_seed() -> u64 {
+    let (year, month, day) = storage::civil_date(storage::unix_time());
+    (year * 10_000 + month * 100 + day) as u64
+}
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SeedOverride>()
+            .init_resource::<GameRng>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(seed_run.label(GameplaySystem::Seed)),
+            );
+    }
+}
+
+fn seed_run(seed_override: Res<SeedOverride>, config: Res<GameConfig>, mut rng: ResMut<GameRng>) {
+    let seed = seed_override.0.or(config.seed).unwrap_or_else(daily_seed);
+    *rng = GameRng::new(seed);
+}
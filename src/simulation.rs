@@ -0,0 +1,232 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_rapier2d::physics::PhysicsSystems;
+use std::time::Duration;
+
+use crate::config::GameConfig;
+use crate::GameState;
+
+/// The most ticks a single update catches up on. Time beyond that is dropped,
+/// so a long stall slows the game down instead of freezing it.
+const MAX_TICKS_PER_UPDATE: u32 = 5;
+
+/// The fixed-rate gameplay tick. Gameplay systems read this instead of
+/// [`Time`], so a run plays out the same way at any frame rate.
+pub struct SimulationTime {
+    step: f32,
+    /// Runs exactly one tick per update instead of keeping up with real time.
+    lockstep: bool,
+    /// Real time not yet simulated.
+    accumulator: f32,
+    /// Ticks still to run in the current update.
+    pending_ticks: u32,
+}
+
+impl Default for SimulationTime {
+    fn default() -> Self {
+        Self {
+            step: 1.0 / 60.0,
+            lockstep: false,
+            accumulator: 0.0,
+            pending_ticks: 0,
+        }
+    }
+}
+
+impl SimulationTime {
+    /// Runs one tick per update, whatever the real frame time.
+    pub fn lockstep() -> Self {
+        Self {
+            lockstep: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f32(self.step)
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.step
+    }
+
+    /// How far the frame being drawn is past the last tick, as a fraction of
+    /// a tick.
+    pub fn overstep(&self) -> f32 {
+        if self.lockstep {
+            1.0
+        } else {
+            self.accumulator / self.step
+        }
+    }
+
+    /// Skips the ticks left in this update, once a tick has ended the run.
+    pub fn stop(&mut self) {
+        self.pending_ticks = 0;
+    }
+}
+
+/// The schedule run once per gameplay tick, after [`CoreStage::Update`].
+#[derive(StageLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub struct GameplayTick;
+
+/// Stages within a gameplay tick.
+#[derive(StageLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum TickStage {
+    /// Transforms go back to where the last tick left them, and physics
+    /// bodies spawned since then are created.
+    Prepare,
+    /// New colliders are attached to their bodies.
+    Attach,
+    Gameplay,
+    /// Physics positions are written back to transforms.
+    Sync,
+}
+
+/// Order of the gameplay systems within a tick. Systems that share data are
+/// ordered explicitly, so a run plays out the same way every time. Physics
+/// steps after `Movement` and before `Reflect`.
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum GameplaySystem {
+    /// Reseeds the run's randomness, before anything draws from it.
+    Seed,
+    /// Physics entities present from the start of a run. Spawned before
+    /// anything else so they always get the same ids.
+    SpawnPlayer,
+    SpawnGrandfathers,
+    Timers,
+    Control,
+    Reflector,
+    Spawn,
+    Movement,
+    Reflect,
+    Hits,
+    /// Marks clocks that have just run out. Part of `Outcome`, but ordered
+    /// before the rest of it, so a grandfather is seen running out before
+    /// endless mode replaces it.
+    Expire,
+    Outcome,
+    /// Ends the run once every player is out. Checked before the win, so
+    /// running out on the same tick as the last grandfather is still a loss.
+    Defeat,
+}
+
+/// Draws the entity between its last two ticks, so movement looks smooth when
+/// the frame rate and tick rate differ.
+#[derive(Component, Default)]
+pub struct Interpolated {
+    previous: Option<Transform>,
+    current: Option<Transform>,
+}
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let mut tick = Schedule::default().with_run_criteria(run_pending_tick.system());
+        tick.add_stage(
+            TickStage::Prepare,
+            SystemStage::parallel().with_system(restore_tick_transforms),
+        )
+        .add_stage(TickStage::Attach, SystemStage::parallel())
+        .add_stage(TickStage::Gameplay, SystemStage::parallel())
+        .add_stage(
+            TickStage::Sync,
+            SystemStage::parallel()
+                .with_system(store_tick_transforms.after(PhysicsSystems::SyncTransforms)),
+        );
+
+        app.init_resource::<SimulationTime>()
+            .add_stage_after(CoreStage::Update, GameplayTick, tick)
+            .add_system_to_stage(CoreStage::PreUpdate, advance_simulation_time)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_transforms.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Adds systems to the gameplay stage of every tick. They only run while
+/// [`GameState::Playing`] is the current state.
+pub fn add_gameplay_systems(app: &mut App, system_set: SystemSet) {
+    add_tick_systems(app, TickStage::Gameplay, system_set);
+}
+
+pub fn add_tick_systems(app: &mut App, stage: TickStage, system_set: SystemSet) {
+    app.stage(GameplayTick, |tick: &mut Schedule| {
+        tick.add_system_set_to_stage(stage, system_set)
+    });
+}
+
+/// Works out how many ticks this update has to run to keep up with real time.
+fn advance_simulation_time(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    config: Option<Res<GameConfig>>,
+    mut simulation_time: ResMut<SimulationTime>,
+) {
+    if let Some(config) = config {
+        simulation_time.step = 1.0 / config.tick_rate;
+    }
+    if *state.current() != GameState::Playing {
+        simulation_time.accumulator = 0.0;
+        simulation_time.pending_ticks = 0;
+        return;
+    }
+    if simulation_time.lockstep {
+        simulation_time.pending_ticks = 1;
+        return;
+    }
+
+    simulation_time.accumulator += time.delta_seconds();
+    let ticks = (simulation_time.accumulator / simulation_time.step) as u32;
+    simulation_time.pending_ticks = ticks.min(MAX_TICKS_PER_UPDATE);
+    simulation_time.accumulator = if ticks > MAX_TICKS_PER_UPDATE {
+        0.0
+    } else {
+        simulation_time.accumulator - ticks as f32 * simulation_time.step
+    };
+}
+
+fn run_pending_tick(
+    state: Res<State<GameState>>,
+    mut simulation_time: ResMut<SimulationTime>,
+) -> ShouldRun {
+    // a pause or the end of a run stops the ticks left in this update
+    if simulation_time.pending_ticks == 0 || *state.current() != GameState::Playing {
+        return ShouldRun::No;
+    }
+    simulation_time.pending_ticks -= 1;
+    ShouldRun::YesAndCheckAgain
+}
+
+/// Puts back where each entity really is before the tick moves it on.
+fn restore_tick_transforms(mut q_interpolated: Query<(&mut Transform, &Interpolated)>) {
+    for (mut transform, interpolated) in q_interpolated.iter_mut() {
+        if let Some(current) = interpolated.current {
+            *transform = current;
+        }
+    }
+}
+
+fn store_tick_transforms(mut q_interpolated: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in q_interpolated.iter_mut() {
+        interpolated.previous = Some(interpolated.current.unwrap_or(*transform));
+        interpolated.current = Some(*transform);
+    }
+}
+
+fn interpolate_transforms(
+    simulation_time: Res<SimulationTime>,
+    mut q_interpolated: Query<(&mut Transform, &Interpolated)>,
+) {
+    let t = simulation_time.overstep().clamp(0.0, 1.0);
+    for (mut transform, interpolated) in q_interpolated.iter_mut() {
+        if let (Some(previous), Some(current)) = (interpolated.previous, interpolated.current) {
+            transform.translation = previous.translation.lerp(current.translation, t);
+            transform.rotation = previous.rotation.slerp(current.rotation, t);
+            transform.scale = previous.scale.lerp(current.scale, t);
+        }
+    }
+}
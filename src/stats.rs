@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::clock::{format_time, Clock};
+use crate::components::Player;
+use crate::difficulty::Difficulty;
+use crate::endless::GameMode;
+use crate::events::{AlarmHitPlayer, AlarmReflected, ClockChanged, PlayerDashed};
+use crate::grandfather::Grandfather;
+use crate::replay::ReplayPlayback;
+use crate::rng::GameRng;
+use crate::simulation::{add_gameplay_systems, GameplaySystem, SimulationTime};
+use crate::GameState;
+
+/// What happened during the current, or most recent, run.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct RunStats {
+    /// Simulated seconds spent playing, not counting pauses.
+    pub elapsed: f32,
+    pub alarms_reflected: u32,
+    pub hits_taken: u32,
+    pub dashes: u32,
+    /// Seconds knocked off each grandfather's clock by alarms, by name.
+    pub grandfather_drain: BTreeMap<String, f32>,
+    /// The least time a player had left just after surviving an alarm hit,
+    /// if any hit was survived.
+    pub closest_call: Option<f32>,
+    /// The most any player had left on their clock when the run ended.
+    pub remaining: f32,
+    /// The seed the run was played with, to share or play it again.
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub mode: GameMode,
+    /// The run was a replay being watched, which is kept out of the high
+    /// scores and does not replace the last replay.
+    pub replay: bool,
+}
+
+impl RunStats {
+    /// An endless run's score: seconds survived plus seconds drained from
+    /// grandfathers.
+    pub fn score(&self) -> f32 {
+        self.elapsed + self.grandfather_drain.values().sum::<f32>()
+    }
+
+    /// The breakdown shown on the end screens, with every figure scaled by
+    /// `progress` so it can count up from zero.
+    pub fn breakdown(&self, progress: f32) -> String {
+        let progress = progress.clamp(0.0, 1.0);
+        let count = |value: u32| (value as f32 * progress).round() as u32;
+        let mut lines = vec![
+            format!("Alarms reflected: {}", count(self.alarms_reflected)),
+            format!("Hits taken: {}", count(self.hits_taken)),
+            format!("Dashes used: {}", count(self.dashes)),
+        ];
+        for (name, drain) in self.grandfather_drain.iter() {
+            lines.push(format!(
+                "Drained from {}: {}",
+                name,
+                format_time(drain * progress)
+            ));
+        }
+        if let Some(closest_call) = self.closest_call {
+            lines.push(format!(
+                "Closest call: {} left",
+                format_time(closest_call * progress)
+            ));
+        }
+        if self.mode == GameMode::Endless {
+            lines.push(format!("Score: {}", count(self.score().round() as u32)));
+        }
+        lines.push(format!("Difficulty: {}", self.difficulty));
+        lines.push(format!("Seed: {}", self.seed));
+        lines.join("\n")
+    }
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>().add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(reset_stats.after(GameplaySystem::Seed)),
+        );
+        add_gameplay_systems(
+            app,
+            SystemSet::new().with_system(record_stats.after(GameplaySystem::Outcome)),
+        );
+    }
+}
+
+fn reset_stats(
+    mut stats: ResMut<RunStats>,
+    rng: Res<GameRng>,
+    difficulty: Res<Difficulty>,
+    mode: Res<GameMode>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    *stats = RunStats {
+        seed: rng.seed(),
+        difficulty: *difficulty,
+        mode: *mode,
+        replay: playback.is_some(),
+        ..Default::default()
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_stats(
+    mut stats: ResMut<RunStats>,
+    time: Res<SimulationTime>,
+    mut ev_reflected: EventReader<AlarmReflected>,
+    mut ev_hit_player: EventReader<AlarmHitPlayer>,
+    mut ev_dashed: EventReader<PlayerDashed>,
+    mut ev_clock_changed: EventReader<ClockChanged>,
+    q_player: Query<&Clock, With<Player>>,
+    q_grandfather: Query<&Name, With<Grandfather>>,
+) {
+    stats.elapsed += time.delta_seconds();
+    stats.alarms_reflected += ev_reflected.iter().count() as u32;
+    for hit in ev_hit_player.iter() {
+        stats.hits_taken += 1;
+        // the clocks have already lost this tick's hits
+        let left = match q_player.get(hit.player) {
+            Ok(clock) if clock.time > 0.0 => clock.time,
+            _ => continue,
+        };
+        stats.closest_call = Some(stats.closest_call.map_or(left, |t| t.min(left)));
+    }
+    stats.dashes += ev_dashed.iter().count() as u32;
+
+    for changed in ev_clock_changed.iter() {
+        if let Ok(name) = q_grandfather.get(changed.entity) {
+            *stats
+                .grandfather_drain
+                .entry(name.as_str().to_string())
+                .or_default() -= changed.delta.min(0.0);
+        }
+    }
+
+    let remaining = q_player.iter().map(|clock| clock.time).reduce(f32::max);
+    if let Some(remaining) = remaining {
+        stats.remaining = remaining;
+    }
+}
@@ -0,0 +1,111 @@
+//! Small text files kept between runs, such as settings and high scores.
+//! Native builds keep them in the platform data directory, the web build in
+//! the browser's `localStorage`.
+
+const GAME_DIR: &str = "wasting-time";
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use super::GAME_DIR;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Where the game keeps its files on this platform.
+    fn data_dir() -> Option<PathBuf> {
+        let base = if cfg!(target_os = "windows") {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME").map(|home| {
+                PathBuf::from(home)
+                    .join("Library")
+                    .join("Application Support")
+            })
+        } else {
+            std::env::var_os("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME")
+                        .map(|home| PathBuf::from(home).join(".local").join("share"))
+                })
+        };
+        base.map(|base| base.join(GAME_DIR))
+    }
+
+    pub fn load(name: &str) -> Option<String> {
+        fs::read_to_string(data_dir()?.join(name)).ok()
+    }
+
+    pub fn save(name: &str, contents: &str) -> Result<(), String> {
+        let dir = data_dir().ok_or_else(|| "no data directory on this platform".to_string())?;
+        fs::create_dir_all(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        let path = dir.join(name);
+        fs::write(&path, contents).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use super::GAME_DIR;
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    fn key(name: &str) -> String {
+        format!("{}/{}", GAME_DIR, name)
+    }
+
+    pub fn load(name: &str) -> Option<String> {
+        local_storage()?.get_item(&key(name)).ok()?
+    }
+
+    pub fn save(name: &str, contents: &str) -> Result<(), String> {
+        let storage = local_storage().ok_or_else(|| "localStorage is unavailable".to_string())?;
+        storage
+            .set_item(&key(name), contents)
+            .map_err(|err| format!("{}: {:?}", key(name), err))
+    }
+}
+
+/// Reads a stored file, or `None` if it was never written.
+pub fn load(name: &str) -> Option<String> {
+    backend::load(name)
+}
+
+pub fn save(name: &str, contents: &str) -> Result<(), String> {
+    backend::save(name, contents)
+}
+
+/// The UTC `(year, month, day)` of a Unix timestamp.
+pub fn civil_date(timestamp: u64) -> (i64, i64, i64) {
+    // days since the epoch to a civil date, after Howard Hinnant's algorithm
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Seconds since the Unix epoch, for dating saved records.
+pub fn unix_time() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        (js_sys::Date::now() / 1000.0) as u64
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs())
+    }
+}
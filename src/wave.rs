@@ -0,0 +1,219 @@
+use crate::alarm::AlarmKind;
+use crate::cleanup::StateScoped;
+use crate::config::{check_positive, ConfigAsset, GameConfig};
+use crate::difficulty::Difficulty;
+use crate::loading::FontAssets;
+use crate::rng::GameRng;
+use crate::simulation::{add_gameplay_systems, GameplaySystem, SimulationTime};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// One phase of a run: while it lasts, every living grandfather throws
+/// `alarm_count` alarms each `spawn_interval` seconds.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Wave {
+    pub duration: f32,
+    pub spawn_interval: f32,
+    pub alarm_count: u32,
+    /// Each alarm is one of these kinds, picked at random. Listing a kind
+    /// more than once makes it more likely.
+    pub alarms: Vec<AlarmKind>,
+    /// Spawn points relative to the grandfather, with x pointing towards the
+    /// middle of the stage. Alarms cycle through them in order.
+    pub spawn_offsets: Vec<(f32, f32)>,
+    /// How far, in pixels along each axis, an alarm may land from its spawn
+    /// point.
+    pub spawn_jitter: f32,
+}
+
+#[derive(Serialize, Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "9e1b3c52-7a4d-4c0f-8b6e-2d5f1a7c3e90"]
+pub struct WaveTimeline {
+    /// How much longer or shorter each wave and its spawn interval may run,
+    /// as a fraction picked at random each time the wave starts.
+    pub variation: f32,
+    pub waves: Vec<Wave>,
+}
+
+impl ConfigAsset for WaveTimeline {
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if !(0.0..1.0).contains(&self.variation) {
+            errors.push(format!(
+                "variation must be at least 0 and below 1, got {}",
+                self.variation
+            ));
+        }
+        if self.waves.is_empty() {
+            errors.push("waves must contain at least one wave".to_string());
+        }
+        for (i, wave) in self.waves.iter().enumerate() {
+            check_positive(
+                &mut errors,
+                &format!("waves[{}].duration", i),
+                wave.duration,
+            );
+            check_positive(
+                &mut errors,
+                &format!("waves[{}].spawn_interval", i),
+                wave.spawn_interval,
+            );
+            if wave.alarm_count > 0 && wave.spawn_offsets.is_empty() {
+                errors.push(format!("waves[{}].spawn_offsets must not be empty", i));
+            }
+            if wave.alarm_count > 0 && wave.alarms.is_empty() {
+                errors.push(format!("waves[{}].alarms must not be empty", i));
+            }
+            if !wave.spawn_jitter.is_finite() || wave.spawn_jitter < 0.0 {
+                errors.push(format!(
+                    "waves[{}].spawn_jitter must not be negative, got {}",
+                    i, wave.spawn_jitter
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The wave being played and the timer driving it.
+pub struct CurrentWave {
+    pub index: usize,
+    pub timer: Timer,
+    /// Seconds between alarms, after this wave's variation.
+    pub spawn_interval: f32,
+}
+
+impl CurrentWave {
+    /// Starts wave `index`, with its spawn interval stretched by
+    /// `interval_scale` for the difficulty.
+    fn new(index: usize, timeline: &WaveTimeline, interval_scale: f32, rng: &mut GameRng) -> Self {
+        let wave = &timeline.waves[index];
+        let mut vary = |seconds: f32| {
+            seconds * (1.0 + rng.gen_range(-timeline.variation..=timeline.variation))
+        };
+        Self {
+            index,
+            timer: Timer::from_seconds(vary(wave.duration), false),
+            spawn_interval: vary(wave.spawn_interval) * interval_scale,
+        }
+    }
+}
+
+pub struct WaveStarted(pub usize);
+
+pub struct WaveCleared(pub usize);
+
+#[derive(Component)]
+struct WaveUI;
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<WaveStarted>()
+            .add_event::<WaveCleared>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(start_first_wave.after(GameplaySystem::Seed))
+                    .with_system(spawn_wave_ui.after(GameplaySystem::SpawnGrandfathers)),
+            )
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(update_wave_ui));
+        add_gameplay_systems(
+            app,
+            SystemSet::new()
+                .with_system(advance_wave.label(GameplaySystem::Timers))
+                .with_system(log_wave_cleared),
+        );
+    }
+}
+
+fn start_first_wave(
+    mut commands: Commands,
+    timeline: Res<WaveTimeline>,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    mut rng: ResMut<GameRng>,
+    mut ev_wave_started: EventWriter<WaveStarted>,
+) {
+    let interval_scale = config.difficulty.scale(*difficulty).spawn_interval;
+    commands.insert_resource(CurrentWave::new(0, &timeline, interval_scale, &mut rng));
+    ev_wave_started.send(WaveStarted(0));
+}
+
+/// Moves on to the next wave once the current one has run its course. The last
+/// wave keeps repeating until the run ends.
+#[allow(clippy::too_many_arguments)]
+fn advance_wave(
+    time: Res<SimulationTime>,
+    timeline: Res<WaveTimeline>,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    mut rng: ResMut<GameRng>,
+    mut current: ResMut<CurrentWave>,
+    mut ev_wave_started: EventWriter<WaveStarted>,
+    mut ev_wave_cleared: EventWriter<WaveCleared>,
+) {
+    if !current.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    ev_wave_cleared.send(WaveCleared(current.index));
+    let next = (current.index + 1).min(timeline.waves.len() - 1);
+    let interval_scale = config.difficulty.scale(*difficulty).spawn_interval;
+    *current = CurrentWave::new(next, &timeline, interval_scale, &mut rng);
+    ev_wave_started.send(WaveStarted(next));
+}
+
+fn log_wave_cleared(mut ev_wave_cleared: EventReader<WaveCleared>) {
+    for wave in ev_wave_cleared.iter() {
+        info!("wave {} cleared", wave.0 + 1);
+    }
+}
+
+fn spawn_wave_ui(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "Wave 1".to_string(),
+                    style: bevy::text::TextStyle {
+                        font: font_assets.roboto.clone(),
+                        font_size: 25.0,
+                        color: Color::rgb(1., 1., 1.),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Playing))
+        .insert(WaveUI);
+}
+
+fn update_wave_ui(
+    mut ev_wave_started: EventReader<WaveStarted>,
+    mut q_text: Query<&mut Text, With<WaveUI>>,
+) {
+    for wave in ev_wave_started.iter() {
+        for mut text in q_text.iter_mut() {
+            text.sections[0].value = format!("Wave {}", wave.0 + 1);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use wasting_time::{GameMode, HighScores, RunRecord};
+
+fn run(won: bool, time: f32) -> RunRecord {
+    RunRecord {
+        won,
+        time,
+        remaining: 10.0,
+        difficulty: "Normal".to_string(),
+        date: 0,
+        mode: GameMode::Standard,
+        score: 0.0,
+    }
+}
+
+fn endless_run(score: f32) -> RunRecord {
+    RunRecord {
+        won: false,
+        mode: GameMode::Endless,
+        score,
+        ..run(false, score / 2.0)
+    }
+}
+
+#[test]
+fn only_a_faster_win_is_a_new_record() {
+    let mut scores = HighScores::default();
+
+    assert!(!scores.record(run(false, 30.0)));
+    assert!(scores.record(run(true, 90.0)));
+    assert!(!scores.record(run(true, 120.0)));
+    assert!(scores.record(run(true, 60.0)));
+
+    let best: Vec<f32> = scores.best_times().iter().map(|run| run.time).collect();
+    assert_eq!(best, vec![60.0, 90.0, 120.0]);
+    assert_eq!(scores.longest_runs().len(), 1);
+}
+
+#[test]
+fn high_scores_survive_a_round_trip() {
+    let mut scores = HighScores::default();
+    scores.record(run(true, 75.5));
+
+    let saved = ron::ser::to_string(&scores).unwrap();
+    let loaded: HighScores = ron::de::from_str(&saved).unwrap();
+
+    assert_eq!(loaded.runs, scores.runs);
+}
+
+#[test]
+fn endless_runs_have_their_own_leaderboard() {
+    let mut scores = HighScores::default();
+    scores.record(run(false, 30.0));
+
+    assert!(scores.record(endless_run(200.0)));
+    assert!(!scores.record(endless_run(150.0)));
+    assert!(scores.record(endless_run(400.0)));
+
+    let best: Vec<f32> = scores
+        .endless_scores()
+        .iter()
+        .map(|run| run.score)
+        .collect();
+    assert_eq!(best, vec![400.0, 200.0, 150.0]);
+    assert_eq!(scores.longest_runs().len(), 1);
+}
+
+#[test]
+fn runs_saved_before_modes_load_as_standard() {
+    let saved =
+        r#"(runs: [(won: true, time: 60.0, remaining: 5.0, difficulty: "Normal", date: 0)])"#;
+    let loaded: HighScores = ron::de::from_str(saved).unwrap();
+
+    assert_eq!(loaded.runs[0].mode, GameMode::Standard);
+    assert_eq!(loaded.best_times().len(), 1);
+}
@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+use wasting_time::{Action, Binding, InputBindings, PlayerId};
+
+#[test]
+fn rebinding_moves_the_binding_between_actions() {
+    let mut bindings = InputBindings::default();
+    let space = Binding::Key(KeyCode::Space);
+    assert!(bindings.bindings(Action::Dash).contains(&space));
+
+    bindings.rebind(Action::Reflect, space);
+
+    assert!(!bindings.bindings(Action::Dash).contains(&space));
+    assert_eq!(bindings.bindings(Action::Reflect)[0], space);
+}
+
+#[test]
+fn bindings_survive_a_round_trip() {
+    let mut bindings = InputBindings::default();
+    bindings.rebind(Action::Pause, Binding::Gamepad(GamepadButtonType::Select));
+
+    let saved = ron::ser::to_string_pretty(&bindings, Default::default()).unwrap();
+    let loaded: InputBindings = ron::de::from_str(&saved).unwrap();
+
+    assert_eq!(loaded.actions, bindings.actions);
+}
+
+#[test]
+fn the_second_player_has_keys_of_their_own() {
+    let first = InputBindings::default_for(PlayerId(0));
+    let second = InputBindings::default_for(PlayerId(1));
+
+    for (action, bindings) in second.actions.iter() {
+        assert!(!bindings.is_empty(), "{:?} is unbound", action);
+        for binding in bindings {
+            if let Binding::Key(_) = binding {
+                assert!(
+                    first.actions.values().all(|first| !first.contains(binding)),
+                    "{:?} is shared",
+                    binding
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use wasting_time::{parse_config, PatternLibrary};
+
+fn parse(patterns: &str) -> Result<PatternLibrary, Vec<String>> {
+    parse_config(format!("(patterns: {{ {} }})", patterns).as_bytes())
+}
+
+#[test]
+fn the_shipped_patterns_are_valid() {
+    parse_config::<PatternLibrary>(include_bytes!("../assets/config/game.patterns.ron")).unwrap();
+}
+
+#[test]
+fn patterns_that_fire_nothing_or_flood_the_stage_are_rejected() {
+    let errors = parse(
+        r#""empty": (radius: 140.0, steps: [Delay(1.0)]),
+        "zero": (radius: 140.0, steps: [Ring(count: 0, speed: 1.0, kind: Homing)]),
+        "flood": (radius: 140.0, steps: [
+            Repeat(times: 100, steps: [Repeat(times: 100, steps: [
+                Fan(count: 3, spread: 30.0, speed: 1.0, kind: StraightShot),
+            ])]),
+        ]),"#,
+    )
+    .unwrap_err();
+
+    assert_eq!(errors.len(), 4, "{:?}", errors);
+    assert!(errors
+        .iter()
+        .any(|error| error.contains(r#"patterns["empty"] never fires"#)));
+    assert!(errors
+        .iter()
+        .any(|error| error.contains(r#"patterns["zero"].steps[0].count must be at least 1"#)));
+    assert!(errors.iter().any(|error| error.contains("30000 shots")));
+}
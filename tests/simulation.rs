@@ -0,0 +1,930 @@
+use bevy::prelude::*;
+use wasting_time::{
+    parse_config, AlarmKind, Clock, Difficulty, GameConfig, GameMode, GameState, Grandfather,
+    HeadlessPlugin, HighScores, LastReplay, PatternLibrary, Player, PlayerCount, PlayerId,
+    PlayerInput, PlayerInputs, ReplayPlayback, RunStats, SeedOverride, WaveTimeline,
+};
+
+/// One gameplay tick at the configured `tick_rate`.
+const TIMESTEP: f32 = 1.0 / 60.0;
+const SEED: u64 = 7;
+
+fn game_config() -> GameConfig {
+    parse_config(include_bytes!("../assets/config/game.config.ron")).unwrap()
+}
+
+fn waves() -> WaveTimeline {
+    parse_config(include_bytes!("../assets/config/game.waves.ron")).unwrap()
+}
+
+fn patterns() -> PatternLibrary {
+    parse_config(include_bytes!("../assets/config/game.patterns.ron")).unwrap()
+}
+
+/// A timeline whose only wave never throws anything.
+fn quiet_waves() -> WaveTimeline {
+    let mut waves = waves();
+    waves.waves.truncate(1);
+    waves.waves[0].alarm_count = 0;
+    waves
+}
+
+fn app(config: GameConfig, waves: WaveTimeline) -> App {
+    seeded_app(config, waves, SEED)
+}
+
+fn seeded_app(config: GameConfig, waves: WaveTimeline, seed: u64) -> App {
+    headless_app(config, waves, patterns(), seed)
+}
+
+fn headless_app(
+    config: GameConfig,
+    waves: WaveTimeline,
+    patterns: PatternLibrary,
+    seed: u64,
+) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins).add_plugin(HeadlessPlugin {
+        config,
+        waves,
+        patterns,
+        seed,
+    });
+    app
+}
+
+fn run_for(app: &mut App, seconds: f32) {
+    for _ in 0..(seconds / TIMESTEP).round() as u32 {
+        app.update();
+    }
+}
+
+/// Changes what `player` asks for, from the next update on.
+fn set_input(app: &mut App, player: usize, change: impl FnOnce(&mut PlayerInput)) {
+    let mut inputs = app.world.get_resource_mut::<PlayerInputs>().unwrap();
+    change(&mut inputs[PlayerId(player)]);
+}
+
+fn player_clock(app: &mut App) -> f32 {
+    app.world
+        .query_filtered::<&Clock, With<Player>>()
+        .iter(&app.world)
+        .next()
+        .unwrap()
+        .time
+}
+
+fn state(app: &App) -> GameState {
+    app.world
+        .get_resource::<State<GameState>>()
+        .unwrap()
+        .current()
+        .clone()
+}
+
+#[test]
+fn player_clock_ticks_with_simulation_time() {
+    let config = game_config();
+    let start = config.player.clock;
+    let mut app = app(config, quiet_waves());
+
+    run_for(&mut app, 2.0);
+
+    let elapsed = start - player_clock(&mut app);
+    assert!((elapsed - 2.0).abs() < 0.1, "elapsed {}", elapsed);
+    assert_eq!(state(&app), GameState::Playing);
+}
+
+#[test]
+fn runs_are_deterministic() {
+    let play = || {
+        let mut app = app(game_config(), waves());
+        for frame in 0..240 {
+            set_input(&mut app, 0, |input| {
+                input.movement = Vec2::new(if frame < 120 { 1.0 } else { -1.0 }, 0.0);
+                input.reflect = frame % 60 < 30;
+            });
+            app.update();
+        }
+        player_clock(&mut app)
+    };
+
+    assert_eq!(play(), play());
+}
+
+#[test]
+fn moving_player_follows_input() {
+    let mut app = app(game_config(), quiet_waves());
+    app.update();
+    let start = player_position(&mut app);
+
+    set_input(&mut app, 0, |input| input.movement = Vec2::new(0.0, 1.0));
+    run_for(&mut app, 0.5);
+
+    assert!(player_position(&mut app).y > start.y);
+}
+
+#[test]
+fn partial_stick_tilt_moves_slower() {
+    let distance = |tilt: f32| {
+        let mut app = app(game_config(), quiet_waves());
+        app.update();
+        let start = player_position(&mut app);
+        set_input(&mut app, 0, |input| input.movement = Vec2::new(0.0, tilt));
+        run_for(&mut app, 0.25);
+        player_position(&mut app).y - start.y
+    };
+
+    let half = distance(0.5);
+    let full = distance(1.0);
+    assert!(
+        half > 0.0 && half < full * 0.75,
+        "half {} full {}",
+        half,
+        full
+    );
+}
+
+fn player_position(app: &mut App) -> Vec3 {
+    app.world
+        .query_filtered::<&Transform, With<Player>>()
+        .iter(&app.world)
+        .next()
+        .unwrap()
+        .translation
+}
+
+#[test]
+fn game_over_when_player_clock_runs_out() {
+    let mut config = game_config();
+    config.player.clock = 1.0;
+    let mut app = app(config, quiet_waves());
+
+    run_for(&mut app, 0.5);
+    assert_eq!(state(&app), GameState::Playing);
+
+    run_for(&mut app, 1.0);
+    assert_eq!(state(&app), GameState::GameOver);
+}
+
+#[test]
+fn leaving_a_run_despawns_only_its_entities() {
+    let mut config = game_config();
+    config.player.clock = 1.0;
+    let mut app = app(config, waves());
+
+    run_for(&mut app, 1.5);
+    assert_eq!(state(&app), GameState::GameOver);
+
+    let players = app
+        .world
+        .query_filtered::<Entity, With<Player>>()
+        .iter(&app.world)
+        .count();
+    let grandfathers = app
+        .world
+        .query_filtered::<Entity, With<Grandfather>>()
+        .iter(&app.world)
+        .count();
+    let cameras = app.world.query::<&Camera>().iter(&app.world).count();
+    assert_eq!((players, grandfathers, cameras), (0, 0, 1));
+}
+
+#[test]
+fn game_win_when_both_grandfathers_run_out() {
+    let mut config = game_config();
+    for grandfather in config.grandfathers.iter_mut() {
+        grandfather.clock = 1.0;
+    }
+    let mut app = app(config, quiet_waves());
+
+    app.update();
+    let grandfathers = app
+        .world
+        .query_filtered::<&Clock, With<Grandfather>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(grandfathers, 2);
+
+    run_for(&mut app, 1.5);
+    assert_eq!(state(&app), GameState::GameWin);
+
+    let scores = app.world.get_resource::<HighScores>().unwrap();
+    assert_eq!(scores.best_times().len(), 1);
+}
+
+#[test]
+fn running_out_with_the_last_grandfather_is_a_loss() {
+    let mut config = game_config();
+    config.player.clock = 1.0;
+    for grandfather in config.grandfathers.iter_mut() {
+        grandfather.clock = 1.0;
+    }
+    let mut app = app(config, quiet_waves());
+
+    while state(&app) == GameState::Playing {
+        app.update();
+    }
+    assert_eq!(state(&app), GameState::GameOver);
+}
+
+#[test]
+fn game_win_waits_for_every_grandfather() {
+    let mut config = game_config();
+    let mut third = config.grandfathers[0].clone();
+    third.name = "Star".to_string();
+    third.position = (0.0, 250.0);
+    third.clock = 2.0;
+    for grandfather in config.grandfathers.iter_mut() {
+        grandfather.clock = 1.0;
+    }
+    config.grandfathers.push(third);
+    let mut app = app(config, quiet_waves());
+
+    app.update();
+    let grandfathers = app
+        .world
+        .query_filtered::<&Clock, With<Grandfather>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(grandfathers, 3);
+
+    run_for(&mut app, 1.5);
+    assert_eq!(state(&app), GameState::Playing);
+    run_for(&mut app, 1.0);
+    assert_eq!(state(&app), GameState::GameWin);
+}
+
+#[test]
+fn pausing_freezes_the_clocks() {
+    let mut app = app(game_config(), quiet_waves());
+    run_for(&mut app, 0.5);
+
+    press_pause(&mut app);
+    assert_eq!(state(&app), GameState::Paused);
+    let paused_at = player_clock(&mut app);
+    run_for(&mut app, 1.0);
+    assert_eq!(player_clock(&mut app), paused_at);
+
+    press_pause(&mut app);
+    assert_eq!(state(&app), GameState::Playing);
+    run_for(&mut app, 0.5);
+    assert!(player_clock(&mut app) < paused_at);
+}
+
+/// Presses what a gamepad's Start and South buttons do at once: confirm,
+/// pause and dash, with the stick held over so the dash would go somewhere.
+fn press_start_and_south(app: &mut App) {
+    set_input(app, 0, |input| {
+        input.movement = Vec2::X;
+        input.confirm = true;
+        input.pause = true;
+        input.dash = true;
+    });
+    app.update();
+}
+
+#[test]
+fn confirming_a_new_run_neither_pauses_nor_dashes() {
+    let mut config = game_config();
+    config.player.clock = 1.0;
+    let mut app = app(config, quiet_waves());
+    while state(&app) == GameState::Playing {
+        app.update();
+    }
+    assert_eq!(state(&app), GameState::GameOver);
+
+    // retry from the game over screen
+    press_start_and_south(&mut app);
+    assert_eq!(state(&app), GameState::Playing);
+    app.update();
+    assert_eq!(app.world.get_resource::<RunStats>().unwrap().dashes, 0);
+
+    // and play from the menu
+    go_to_menu(&mut app);
+    press_start_and_south(&mut app);
+    assert_eq!(state(&app), GameState::Playing);
+    app.update();
+    assert_eq!(app.world.get_resource::<RunStats>().unwrap().dashes, 0);
+}
+
+fn go_to_menu(app: &mut App) {
+    app.world
+        .get_resource_mut::<State<GameState>>()
+        .unwrap()
+        .set(GameState::Menu)
+        .unwrap();
+    app.update();
+}
+
+/// Moves the menu focus `step` buttons down, one press per update.
+fn step_menu_focus(app: &mut App, step: i32) {
+    for _ in 0..step.abs() {
+        set_input(app, 0, |input| input.menu_step = step.signum());
+        app.update();
+    }
+}
+
+#[test]
+fn the_whole_menu_is_reachable_without_a_mouse() {
+    let mut app = app(game_config(), quiet_waves());
+    app.update();
+    go_to_menu(&mut app);
+
+    // from Play, two down is Difficulty, which stays on the menu
+    step_menu_focus(&mut app, 2);
+    set_input(&mut app, 0, |input| input.confirm = true);
+    app.update();
+    assert_eq!(state(&app), GameState::Menu);
+    assert_eq!(
+        *app.world.get_resource::<Difficulty>().unwrap(),
+        Difficulty::Normal.next()
+    );
+
+    // and the focus wraps from Play up to Players
+    step_menu_focus(&mut app, -2);
+    step_menu_focus(&mut app, -1);
+    set_input(&mut app, 0, |input| input.confirm = true);
+    app.update();
+    assert_eq!(app.world.get_resource::<PlayerCount>().unwrap().0, 2);
+
+    step_menu_focus(&mut app, 2);
+    set_input(&mut app, 0, |input| input.confirm = true);
+    app.update();
+    assert_eq!(state(&app), GameState::HighScores);
+}
+
+fn press_pause(app: &mut App) {
+    set_input(app, 0, |input| input.pause = true);
+    app.update();
+}
+
+#[test]
+fn run_stats_count_what_happened() {
+    let config = game_config();
+    let start = config.player.clock;
+    let mut app = app(config, quiet_waves());
+    app.update();
+
+    set_input(&mut app, 0, |input| {
+        input.movement = Vec2::new(1.0, 0.0);
+        input.dash = true;
+    });
+    app.update();
+    set_input(&mut app, 0, |input| input.movement = Vec2::ZERO);
+    run_for(&mut app, 1.0);
+
+    // time spent paused is not part of the run
+    press_pause(&mut app);
+    run_for(&mut app, 1.0);
+    press_pause(&mut app);
+
+    let stats = app.world.get_resource::<RunStats>().unwrap().clone();
+    assert!(
+        (stats.elapsed - 1.0).abs() < 0.1,
+        "elapsed {}",
+        stats.elapsed
+    );
+    assert_eq!((stats.dashes, stats.hits_taken), (1, 0));
+    // no hit was survived, so there was no close call
+    assert_eq!(stats.closest_call, None);
+    assert!((start - stats.remaining - stats.elapsed).abs() < 0.1);
+}
+
+#[test]
+fn closest_call_is_the_time_left_after_a_hit() {
+    let mut app = app(game_config(), waves());
+    // the player stands still in the middle until an alarm reaches them
+    let mut ticks = 0;
+    while app.world.get_resource::<RunStats>().unwrap().hits_taken == 0 {
+        app.update();
+        ticks += 1;
+        assert!(ticks < 3600, "no alarm hit the player");
+    }
+
+    let left = player_clock(&mut app);
+    let stats = app.world.get_resource::<RunStats>().unwrap();
+    assert_eq!(stats.closest_call, Some(left));
+}
+
+#[test]
+fn replays_play_back_the_same_run() {
+    let mut config = game_config();
+    config.player.clock = 4.0;
+    let mut recorded = app(config, waves());
+    let mut frame = 0;
+    while state(&recorded) == GameState::Playing {
+        set_input(&mut recorded, 0, |input| {
+            input.movement = Vec2::new(if frame % 120 < 60 { 1.0 } else { -1.0 }, 0.5);
+            input.reflect = frame % 50 < 25;
+            input.dash = frame % 90 == 0;
+        });
+        recorded.update();
+        frame += 1;
+    }
+    let replay = recorded
+        .world
+        .get_resource::<LastReplay>()
+        .unwrap()
+        .0
+        .clone()
+        .unwrap();
+    // one frame per tick, and the update that leaves the run has none
+    assert_eq!(replay.frames.len(), frame - 1);
+
+    assert_eq!(replay.seed, SEED);
+
+    let mut replayed = seeded_app(replay.config.clone(), replay.waves.clone(), replay.seed);
+    replayed.insert_resource(ReplayPlayback::new(replay));
+    while state(&replayed) == GameState::Playing {
+        replayed.update();
+    }
+
+    assert_eq!(state(&replayed), state(&recorded));
+    let stats = replayed.world.get_resource::<RunStats>().unwrap().clone();
+    assert!(stats.replay);
+    assert_eq!(
+        &RunStats {
+            replay: false,
+            ..stats
+        },
+        recorded.world.get_resource::<RunStats>().unwrap()
+    );
+}
+
+#[test]
+fn replays_give_the_players_settings_back() {
+    let mut config = game_config();
+    config.player.clock = 1.0;
+    let mut app = app(config, quiet_waves());
+    while state(&app) == GameState::Playing {
+        app.update();
+    }
+    let replay = app
+        .world
+        .get_resource::<LastReplay>()
+        .unwrap()
+        .0
+        .clone()
+        .unwrap();
+
+    // the player has since picked their own settings on the menu
+    app.insert_resource(Difficulty::Hard);
+    app.insert_resource(SeedOverride(None));
+    app.world
+        .get_resource_mut::<State<GameState>>()
+        .unwrap()
+        .set(GameState::Menu)
+        .unwrap();
+    app.insert_resource(ReplayPlayback::new(replay.clone()));
+    while state(&app) != GameState::Playing {
+        app.update();
+    }
+    assert_eq!(
+        *app.world.get_resource::<Difficulty>().unwrap(),
+        Difficulty::Normal
+    );
+
+    while state(&app) == GameState::Playing {
+        app.update();
+    }
+    assert_eq!(
+        *app.world.get_resource::<Difficulty>().unwrap(),
+        Difficulty::Hard
+    );
+    assert_eq!(app.world.get_resource::<SeedOverride>().unwrap().0, None);
+    assert!(app.world.get_resource::<ReplayPlayback>().is_none());
+
+    // watching it kept both the replay and the high scores as they were
+    let last = app.world.get_resource::<LastReplay>().unwrap().0.clone();
+    let last = last.unwrap();
+    assert_eq!(last.difficulty, Difficulty::Normal);
+    assert_eq!((last.seed, &last.frames), (replay.seed, &replay.frames));
+    assert_eq!(
+        app.world.get_resource::<HighScores>().unwrap().runs.len(),
+        1
+    );
+}
+
+/// Where everything but the player is on each update, until just after the
+/// first alarms have spawned.
+fn positions_over_first_spawn(seed: u64) -> Vec<Vec3> {
+    let mut app = seeded_app(game_config(), waves(), seed);
+    let mut positions = Vec::new();
+    for _ in 0..(4.0 / TIMESTEP) as u32 {
+        app.update();
+        positions.extend(
+            app.world
+                .query_filtered::<&Transform, Without<Player>>()
+                .iter(&app.world)
+                .map(|transform| transform.translation),
+        );
+    }
+    positions
+}
+
+#[test]
+fn the_seed_decides_the_run() {
+    let run = positions_over_first_spawn(1);
+    assert_eq!(run, positions_over_first_spawn(1));
+    assert_ne!(run, positions_over_first_spawn(2));
+}
+
+/// Whether each grandfather is between phases, and how it is tinted.
+fn grandfather_phases(app: &mut App) -> Vec<(bool, Color)> {
+    app.world
+        .query::<(&Grandfather, &Sprite)>()
+        .iter(&app.world)
+        .map(|(grandfather, sprite)| (grandfather.is_invulnerable(), sprite.color))
+        .collect()
+}
+
+#[test]
+fn grandfathers_change_phase_as_their_clocks_run_down() {
+    let mut config = game_config();
+    for grandfather in config.grandfathers.iter_mut() {
+        grandfather.clock = 10.0;
+    }
+    let transition = config.grandfather.phase_transition;
+    let mut app = app(config, quiet_waves());
+
+    // still above the first threshold at 66%
+    run_for(&mut app, 3.0);
+    assert!(grandfather_phases(&mut app)
+        .iter()
+        .all(|&(invulnerable, color)| !invulnerable && color == Color::WHITE));
+
+    run_for(&mut app, 0.5);
+    assert!(grandfather_phases(&mut app)
+        .iter()
+        .all(|&(invulnerable, color)| invulnerable && color != Color::WHITE));
+
+    run_for(&mut app, transition);
+    assert!(grandfather_phases(&mut app)
+        .iter()
+        .all(|&(invulnerable, _)| !invulnerable));
+}
+
+fn grandfather_positions(app: &mut App) -> Vec<Vec2> {
+    app.world
+        .query_filtered::<&Transform, With<Grandfather>>()
+        .iter(&app.world)
+        .map(|transform| transform.translation.truncate())
+        .collect()
+}
+
+#[test]
+fn grandfathers_move_about_the_stage() {
+    let mut app = app(game_config(), quiet_waves());
+    run_for(&mut app, 0.1);
+    let start = grandfather_positions(&mut app);
+
+    // long enough for the Moon to teleport at least once
+    run_for(&mut app, 8.0);
+    let end = grandfather_positions(&mut app);
+    assert_eq!(start.len(), end.len());
+    for (start, end) in start.iter().zip(end.iter()) {
+        assert!(start.distance(*end) > 10.0, "{} stayed at {}", start, end);
+    }
+}
+
+#[test]
+fn grandfathers_back_away_when_low_on_time() {
+    let mut config = game_config();
+    for (i, grandfather) in config.grandfathers.iter_mut().enumerate() {
+        grandfather.position = (if i == 0 { -100.0 } else { 100.0 }, 50.0);
+        grandfather.retreat.as_mut().unwrap().below = 1.0;
+    }
+    let mut app = app(config, quiet_waves());
+    app.update();
+    let start = grandfather_positions(&mut app);
+
+    // the player stands still in the middle
+    run_for(&mut app, 1.0);
+    let end = grandfather_positions(&mut app);
+    for (start, end) in start.iter().zip(end.iter()) {
+        assert!(end.length() > start.length() + 50.0, "{} -> {}", start, end);
+    }
+}
+
+fn alarm_count(app: &mut App) -> usize {
+    app.world.query::<&AlarmKind>().iter(&app.world).count()
+}
+
+#[derive(Default)]
+struct AlarmsThrown(usize);
+
+fn count_alarms_thrown(mut thrown: ResMut<AlarmsThrown>, q_alarms: Query<(), Added<AlarmKind>>) {
+    thrown.0 += q_alarms.iter().count();
+}
+
+#[test]
+fn grandfathers_keep_throwing_through_the_first_wave() {
+    let mut app = app(game_config(), waves());
+    app.init_resource::<AlarmsThrown>()
+        .add_system(count_alarms_thrown);
+
+    // the first wave throws one alarm from each grandfather every 3s or so
+    run_for(&mut app, 20.0);
+    let thrown = app.world.get_resource::<AlarmsThrown>().unwrap().0;
+    assert!(thrown >= 10, "{} alarms thrown", thrown);
+}
+
+#[test]
+fn grandfathers_throw_their_bullet_patterns() {
+    let mut config = game_config();
+    for grandfather in config.grandfathers.iter_mut() {
+        grandfather.pattern = Some("ring".to_string());
+    }
+    let patterns = parse_config(
+        br#"(
+            patterns: {
+                "ring": (
+                    radius: 140.0,
+                    steps: [
+                        Ring(count: 8, speed: 0.1, kind: StraightShot),
+                        Delay(0.25),
+                        Ring(count: 8, speed: 0.1, kind: StraightShot),
+                    ],
+                ),
+            },
+        )"#,
+    )
+    .unwrap();
+    let mut app = headless_app(config, quiet_waves(), patterns, SEED);
+
+    // a whole ring at once from each grandfather when their timers fire
+    while alarm_count(&mut app) == 0 {
+        app.update();
+    }
+    assert_eq!(alarm_count(&mut app), 16);
+
+    // and the second ring after the delay
+    run_for(&mut app, 0.2);
+    assert_eq!(alarm_count(&mut app), 16);
+    run_for(&mut app, 0.1);
+    assert_eq!(alarm_count(&mut app), 32);
+}
+
+#[test]
+fn pattern_shots_pass_through_the_grandfather_throwing_them() {
+    let mut config = game_config();
+    for grandfather in config.grandfathers.iter_mut() {
+        grandfather.pattern = Some("burst".to_string());
+    }
+    // every shot starts out inside its grandfather
+    let patterns = parse_config(
+        br#"(
+            patterns: {
+                "burst": (
+                    radius: 10.0,
+                    steps: [Ring(count: 4, speed: 0.1, kind: StraightShot)],
+                ),
+            },
+        )"#,
+    )
+    .unwrap();
+    let mut app = headless_app(config, quiet_waves(), patterns, SEED);
+    app.update();
+    let start = grandfather_clocks(&mut app);
+
+    let mut ticks = 0;
+    while alarm_count(&mut app) == 0 {
+        app.update();
+        ticks += 1;
+    }
+    run_for(&mut app, 0.5);
+    let elapsed = ticks as f32 * TIMESTEP + 0.5;
+    for (start, end) in start.iter().zip(grandfather_clocks(&mut app)) {
+        assert!((start - end - elapsed).abs() < 0.05, "{} -> {}", start, end);
+    }
+}
+
+fn player_position_and_nearest_alarm(app: &mut App) -> (Vec2, Option<Vec2>) {
+    let player = player_position(app).truncate();
+    let alarm = app
+        .world
+        .query_filtered::<&Transform, With<AlarmKind>>()
+        .iter(&app.world)
+        .map(|transform| transform.translation.truncate())
+        .min_by(|a, b| a.distance(player).partial_cmp(&b.distance(player)).unwrap());
+    (player, alarm)
+}
+
+#[test]
+fn a_parried_alarm_hurts_the_grandfather() {
+    let mut app = app(game_config(), waves());
+
+    // the reflector goes up facing the first alarm just before it lands
+    while alarm_count(&mut app) == 0 {
+        app.update();
+    }
+    loop {
+        app.update();
+        let (player, alarm) = player_position_and_nearest_alarm(&mut app);
+        if let Some(alarm) = alarm.filter(|alarm| alarm.distance(player) < 70.0) {
+            set_input(&mut app, 0, |input| {
+                input.reflect = true;
+                input.aim = Some(alarm - player);
+            });
+            break;
+        }
+    }
+    let reflected = |app: &App| {
+        app.world
+            .get_resource::<RunStats>()
+            .unwrap()
+            .alarms_reflected
+    };
+    while reflected(&app) == 0 {
+        app.update();
+    }
+    set_input(&mut app, 0, |input| input.reflect = false);
+
+    let start = grandfather_clocks(&mut app);
+    run_for(&mut app, 2.0);
+    let lost: f32 = start
+        .iter()
+        .zip(grandfather_clocks(&mut app))
+        .map(|(start, end)| start - end)
+        .sum();
+    let hit_penalty = game_config().grandfather.hit_penalty;
+    assert!(lost > hit_penalty, "{}s lost", lost);
+}
+
+#[test]
+fn difficulty_scales_the_run_and_is_recorded() {
+    let mut config = game_config();
+    config.player.clock = 2.0;
+    let story = config.difficulty.story.clone();
+    let mut app = app(config.clone(), quiet_waves());
+    app.insert_resource(Difficulty::Story);
+    app.update();
+
+    let expected = config.player.clock * story.player_clock - TIMESTEP;
+    assert!((player_clock(&mut app) - expected).abs() < 0.01);
+    let grandfather_clocks: Vec<f32> = app
+        .world
+        .query_filtered::<&Clock, With<Grandfather>>()
+        .iter(&app.world)
+        .map(|clock| clock.time)
+        .collect();
+    for (clock, spec) in grandfather_clocks.iter().zip(config.grandfathers.iter()) {
+        let expected = spec.clock * story.grandfather_clock - TIMESTEP;
+        assert!((clock - expected).abs() < 0.01, "{} != {}", clock, expected);
+    }
+
+    while state(&app) == GameState::Playing {
+        app.update();
+    }
+    let stats = app.world.get_resource::<RunStats>().unwrap();
+    assert_eq!(stats.difficulty, Difficulty::Story);
+    assert!(stats.breakdown(1.0).contains("Difficulty: Story"));
+    let scores = app.world.get_resource::<HighScores>().unwrap();
+    assert_eq!(scores.runs.last().unwrap().difficulty, "Story");
+}
+
+fn grandfather_clocks(app: &mut App) -> Vec<f32> {
+    app.world
+        .query_filtered::<&Clock, With<Grandfather>>()
+        .iter(&app.world)
+        .map(|clock| clock.time)
+        .collect()
+}
+
+#[test]
+fn endless_mode_replaces_beaten_grandfathers_until_the_player_runs_out() {
+    let mut config = game_config();
+    config.player.clock = 3.0;
+    for grandfather in config.grandfathers.iter_mut() {
+        grandfather.clock = 1.0;
+    }
+    let growth = config.endless.clock_growth;
+    let mut app = app(config, quiet_waves());
+    app.insert_resource(GameMode::Endless);
+
+    // both clocks ran out after a second and came back longer
+    run_for(&mut app, 1.5);
+    assert_eq!(state(&app), GameState::Playing);
+    for clock in grandfather_clocks(&mut app) {
+        let expected = growth - 0.5;
+        assert!((clock - expected).abs() < 0.05, "{} != {}", clock, expected);
+    }
+
+    while state(&app) == GameState::Playing {
+        app.update();
+    }
+    assert_eq!(state(&app), GameState::GameOver);
+    let stats = app.world.get_resource::<RunStats>().unwrap().clone();
+    assert_eq!(stats.mode, GameMode::Endless);
+    assert!(stats.score() >= stats.elapsed);
+    let scores = app.world.get_resource::<HighScores>().unwrap();
+    assert_eq!(scores.endless_scores().len(), 1);
+    assert_eq!(scores.endless_scores()[0].score, stats.score());
+    assert!(scores.longest_runs().is_empty());
+}
+
+fn banner_count(app: &mut App) -> usize {
+    app.world
+        .query::<&Text>()
+        .iter(&app.world)
+        .filter(|text| text.sections[0].value == "New record!")
+        .count()
+}
+
+#[test]
+fn a_record_set_in_a_loss_leaves_with_the_game_over_screen() {
+    let mut config = game_config();
+    config.player.clock = 1.0;
+    let mut app = app(config, quiet_waves());
+    // the first endless score is always a record, though the run is lost
+    app.insert_resource(GameMode::Endless);
+
+    while state(&app) == GameState::Playing {
+        app.update();
+    }
+    assert_eq!(state(&app), GameState::GameOver);
+    app.update();
+    assert_eq!(banner_count(&mut app), 1);
+
+    app.world
+        .get_resource_mut::<State<GameState>>()
+        .unwrap()
+        .set(GameState::Menu)
+        .unwrap();
+    app.update();
+    assert_eq!(banner_count(&mut app), 0);
+}
+
+fn coop_app(config: GameConfig, waves: WaveTimeline) -> App {
+    let mut app = app(config, waves);
+    app.insert_resource(PlayerCount(2));
+    app.update();
+    app
+}
+
+/// Each player's position, in `PlayerId` order.
+fn player_positions(app: &mut App) -> Vec<Vec2> {
+    let mut players: Vec<(PlayerId, Vec2)> = app
+        .world
+        .query_filtered::<(&PlayerId, &Transform), With<Player>>()
+        .iter(&app.world)
+        .map(|(id, transform)| (*id, transform.translation.truncate()))
+        .collect();
+    players.sort_by_key(|(id, _)| *id);
+    players.into_iter().map(|(_, position)| position).collect()
+}
+
+#[test]
+fn each_coop_player_follows_their_own_input() {
+    let mut app = coop_app(game_config(), quiet_waves());
+    let start = player_positions(&mut app);
+    assert_eq!(start.len(), 2);
+    assert_ne!(start[0], start[1]);
+
+    set_input(&mut app, 1, |input| input.movement = Vec2::new(0.0, 1.0));
+    run_for(&mut app, 0.5);
+
+    let end = player_positions(&mut app);
+    assert!(
+        (end[0] - start[0]).length() < 1.0,
+        "{} -> {}",
+        start[0],
+        end[0]
+    );
+    assert!(end[1].y > start[1].y + 10.0, "{} -> {}", start[1], end[1]);
+}
+
+#[test]
+fn coop_game_over_waits_for_every_player() {
+    let mut config = game_config();
+    config.player.clock = 2.0;
+    let mut app = coop_app(config, waves());
+    for (id, mut clock) in app
+        .world
+        .query_filtered::<(&PlayerId, &mut Clock), With<Player>>()
+        .iter_mut(&mut app.world)
+    {
+        if *id == PlayerId(0) {
+            clock.time = 0.5;
+        }
+    }
+
+    // the first player is out, but the second carries on
+    run_for(&mut app, 1.0);
+    assert_eq!(state(&app), GameState::Playing);
+
+    while state(&app) == GameState::Playing {
+        app.update();
+    }
+    assert_eq!(state(&app), GameState::GameOver);
+    let replay = app
+        .world
+        .get_resource::<LastReplay>()
+        .unwrap()
+        .0
+        .clone()
+        .unwrap();
+    assert_eq!(replay.players, 2);
+    assert!(replay.frames.iter().all(|frame| frame.len() == 2));
+}